@@ -1,25 +1,64 @@
 use crate::{
     config::{PjiConfig, PjiMetadata},
+    constant::{APP_GIT_LOG_NAME, DEFAULT_TUI_COMMAND, GIT_LOG_ENV},
     error::PjiError,
+    git::{GitRunner, DEFAULT_CLONE_TIMEOUT_SECS, DEFAULT_GIT_TIMEOUT_SECS},
+    provider::Provider,
     repo::{GitProtocol, GitURI, PjiRepo},
-    util::{list_dir, parse_git_url, try_get_repo_from_dir},
+    util::{
+        ahead_behind, changed_file_count, committed_since, current_branch, default_branch,
+        encode_url_path, has_ssh_credentials, has_uncommitted_changes, head_commit,
+        last_commit_time, latest_tag, list_dir, move_dir, parse_git_url, preferred_clone_url,
+        redact_url_credentials, relative_to_root, repo_root_of, try_get_repo_from_dir,
+        upstream_remote_url,
+    },
     worktree::{
         self, add_worktree, get_default_worktree_path, get_main_repo_from_worktree,
         is_linked_worktree, list_local_branches, list_remote_branches, list_worktrees,
-        prune_worktrees, remove_worktree,
+        lock_worktree, move_worktree, prune_worktrees, remove_empty_worktrees_dir, remove_worktree,
+        unlock_worktree,
     },
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+/// Threads used by `scan`, `verify`, and `status` to run git. They mostly
+/// wait on git subprocesses, so this is more than a typical CPU count.
+const GIT_WORKERS: usize = 8;
+
+/// How many directories below a root a deep scan descends looking for
+/// repositories. The standard layout sits at depth 3.
+const DEEP_SCAN_MAX_DEPTH: usize = 6;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
 pub enum Protocol {
-    #[serde(rename = "SSH", alias = "Ssh")]
     Ssh,
-    #[serde(rename = "HTTP", alias = "Https")]
     Https,
+    /// `git://` URLs, as served by some read-only mirrors.
+    Git,
+    /// Any protocol this version of pji doesn't know, as it was written.
+    Unknown(String),
+}
+
+impl From<String> for Protocol {
+    fn from(value: String) -> Self {
+        GitProtocol::from(value).into()
+    }
+}
+
+impl From<Protocol> for String {
+    fn from(protocol: Protocol) -> Self {
+        GitProtocol::from(protocol).into()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -35,7 +74,13 @@ impl GitUrl {
     pub fn parse(url: &str) -> Result<Self, PjiError> {
         parse_git_url(url)
             .map(Self::from)
-            .ok_or_else(|| PjiError::InvalidGitUrl(url.to_string()))
+            .ok_or_else(|| PjiError::InvalidGitUrl(redact_url_credentials(url)))
+    }
+
+    /// The original URL with any embedded credentials replaced by `***`, for
+    /// display. Clone from `original`.
+    pub fn redacted(&self) -> String {
+        redact_url_credentials(&self.original)
     }
 }
 
@@ -46,52 +91,416 @@ pub struct Repository {
     pub root: PathBuf,
     pub created_at: DateTime<Utc>,
     pub last_opened_at: DateTime<Utc>,
+    /// How many times the repository was opened through pji.
+    #[serde(default)]
+    pub open_count: u32,
+    /// Hosting service used to build web URLs; `None` for unknown hosts.
+    #[serde(default)]
+    pub provider: Option<Provider>,
+    /// Provider set for this repository alone, which wins over the
+    /// `providers` config and the well-known hosts.
+    #[serde(default)]
+    pub provider_override: Option<Provider>,
+    /// Host used in web URLs instead of `git.hostname`, set when the hostname
+    /// is an SSH alias listed in the `host_aliases` config.
+    #[serde(default)]
+    pub web_host: Option<String>,
+    /// Branch `origin/HEAD` pointed at when pji last asked git; `None` until
+    /// known.
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// Labels such as `work` or `oss` set with `pji tag`.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl Repository {
+    /// Ranks how likely the repository is to be opened next: the open count,
+    /// weighted by how recently it was last opened.
+    pub fn frecency(&self, now: DateTime<Utc>) -> f64 {
+        let age = now.signed_duration_since(self.last_opened_at);
+        let recency = if age <= chrono::TimeDelta::hours(1) {
+            4.0
+        } else if age <= chrono::TimeDelta::days(1) {
+            2.0
+        } else if age <= chrono::TimeDelta::weeks(1) {
+            0.5
+        } else {
+            0.25
+        };
+        (f64::from(self.open_count) + 1.0) * recency
+    }
+
+    fn web_url(&self) -> Option<String> {
+        let host = self.web_host.as_ref().unwrap_or(&self.git.hostname);
+        let (owner, name) = (&self.git.owner, &self.git.name);
+        match self.provider? {
+            Provider::GitHub | Provider::GitLab | Provider::Bitbucket | Provider::Gitea => {
+                Some(format!("https://{host}/{owner}/{name}"))
+            }
+            Provider::BitbucketServer => Some(format!(
+                "https://{host}/projects/{}/repos/{name}",
+                owner.to_uppercase()
+            )),
+        }
+    }
+
     pub fn home_url(&self) -> Option<String> {
-        match self.git.hostname.as_str() {
-            "github.com" => Some(format!(
-                "https://github.com/{}/{}",
-                self.git.owner, self.git.name
+        let base = self.web_url()?;
+        match self.provider? {
+            Provider::GitHub | Provider::GitLab | Provider::Bitbucket | Provider::Gitea => {
+                Some(base)
+            }
+            Provider::BitbucketServer => Some(format!("{base}/browse")),
+        }
+    }
+
+    pub fn issue_url(&self, issue: Option<u32>) -> Option<String> {
+        let base = self.web_url()?;
+        let list = match self.provider? {
+            Provider::GitHub | Provider::Bitbucket | Provider::Gitea => format!("{base}/issues"),
+            Provider::GitLab => format!("{base}/-/issues"),
+            // Bitbucket Server has no issue tracker of its own.
+            Provider::BitbucketServer => return None,
+        };
+        Some(match issue {
+            Some(issue) => format!("{list}/{issue}"),
+            None => list,
+        })
+    }
+
+    pub fn pull_request_url(&self, pr: Option<u32>) -> Option<String> {
+        let base = self.web_url()?;
+        match (self.provider?, pr) {
+            (Provider::GitHub, Some(pr)) => Some(format!("{base}/pull/{pr}")),
+            (Provider::GitHub, None) => Some(format!("{base}/pull")),
+            // GitLab calls pull requests merge requests.
+            (Provider::GitLab, Some(pr)) => Some(format!("{base}/-/merge_requests/{pr}")),
+            (Provider::GitLab, None) => Some(format!("{base}/-/merge_requests")),
+            (Provider::Gitea, Some(pr)) => Some(format!("{base}/pulls/{pr}")),
+            (Provider::Gitea, None) => Some(format!("{base}/pulls")),
+            (Provider::Bitbucket, Some(pr)) => Some(format!("{base}/pull-requests/{pr}")),
+            (Provider::BitbucketServer, Some(pr)) => {
+                Some(format!("{base}/pull-requests/{pr}/overview"))
+            }
+            (Provider::Bitbucket | Provider::BitbucketServer, None) => {
+                Some(format!("{base}/pull-requests"))
+            }
+        }
+    }
+
+    /// The pull request page of `branch`. GitHub redirects `pull/new` to the
+    /// form that opens one, which links to an already open pull request.
+    /// `None` where the provider has no such page.
+    pub fn branch_pull_request_url(&self, branch: &str) -> Option<String> {
+        let base = self.web_url()?;
+        let branch = encode_url_path(branch);
+        match self.provider? {
+            Provider::GitHub => Some(format!("{base}/pull/new/{branch}")),
+            Provider::GitLab => Some(format!(
+                "{base}/-/merge_requests/new?merge_request%5Bsource_branch%5D={branch}"
+            )),
+            Provider::Bitbucket => Some(format!("{base}/pull-requests/new?source={branch}")),
+            Provider::Gitea | Provider::BitbucketServer => None,
+        }
+    }
+
+    /// Links to `#number` without knowing whether it's an issue or a pull
+    /// request. Returns `None` when the provider numbers them separately, so the
+    /// caller has to choose.
+    pub fn reference_url(&self, number: u32) -> Option<String> {
+        match self.provider? {
+            // Issues and pull requests share numbers on GitHub, and each URL
+            // redirects to the other kind when needed.
+            Provider::GitHub => self.pull_request_url(Some(number)),
+            // Gitea redirects issue URLs of pull requests, but not the reverse.
+            Provider::Gitea => self.issue_url(Some(number)),
+            Provider::GitLab | Provider::Bitbucket | Provider::BitbucketServer => None,
+        }
+    }
+
+    /// Links to the diff of `path` in the files tab of a pull request.
+    pub fn pull_request_file_url(&self, pr: u32, path: &str) -> Option<String> {
+        match self.provider? {
+            // GitHub anchors each file diff with the hex sha256 of its path.
+            Provider::GitHub => Some(format!(
+                "{}/pull/{}/files#diff-{}",
+                self.web_url()?,
+                pr,
+                Sha256::digest(path.as_bytes())
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<String>()
             )),
             _ => None,
         }
     }
 
-    pub fn issue_url(&self, issue: Option<u32>) -> Option<String> {
-        match self.git.hostname.as_str() {
-            "github.com" => match issue {
-                Some(issue) => Some(format!(
-                    "https://github.com/{}/{}/issues/{}",
-                    self.git.owner, self.git.name, issue
-                )),
-                None => Some(format!(
-                    "https://github.com/{}/{}/issues",
-                    self.git.owner, self.git.name
-                )),
-            },
+    /// Shows the files on `branch`.
+    pub fn branch_url(&self, branch: &str) -> Option<String> {
+        let base = self.web_url()?;
+        match self.provider? {
+            Provider::GitHub => Some(format!("{base}/tree/{branch}")),
+            Provider::GitLab => Some(format!("{base}/-/tree/{branch}")),
+            Provider::Gitea => Some(format!("{base}/src/branch/{branch}")),
+            Provider::Bitbucket => Some(format!("{base}/src/{branch}")),
+            Provider::BitbucketServer => Some(format!("{base}/browse?at=refs/heads/{branch}")),
+        }
+    }
+
+    /// Shows `path` (relative to the repository root) on `branch`, optionally
+    /// highlighting `line`.
+    pub fn blob_url(&self, branch: &str, path: &str, line: Option<u32>) -> Option<String> {
+        let base = self.web_url()?;
+        let path = encode_url_path(path);
+        let provider = self.provider?;
+        let url = match provider {
+            Provider::GitHub => format!("{base}/blob/{branch}/{path}"),
+            Provider::GitLab => format!("{base}/-/blob/{branch}/{path}"),
+            Provider::Gitea => format!("{base}/src/branch/{branch}/{path}"),
+            Provider::Bitbucket => format!("{base}/src/{branch}/{path}"),
+            Provider::BitbucketServer => format!("{base}/browse/{path}?at=refs/heads/{branch}"),
+        };
+        let Some(line) = line else {
+            return Some(url);
+        };
+        let anchor = match provider {
+            Provider::GitHub | Provider::GitLab | Provider::Gitea => format!("L{line}"),
+            Provider::Bitbucket => format!("lines-{line}"),
+            Provider::BitbucketServer => line.to_string(),
+        };
+        Some(format!("{url}#{anchor}"))
+    }
+
+    pub fn commit_url(&self, sha: &str) -> Option<String> {
+        let base = self.web_url()?;
+        match self.provider? {
+            Provider::GitHub | Provider::Gitea => Some(format!("{base}/commit/{sha}")),
+            Provider::GitLab => Some(format!("{base}/-/commit/{sha}")),
+            Provider::Bitbucket | Provider::BitbucketServer => {
+                Some(format!("{base}/commits/{sha}"))
+            }
+        }
+    }
+
+    pub fn compare_url(&self, base: &str, head: &str) -> Option<String> {
+        match self.provider? {
+            Provider::GitHub | Provider::Gitea => {
+                Some(format!("{}/compare/{base}...{head}", self.web_url()?))
+            }
+            Provider::GitLab => Some(format!("{}/-/compare/{base}...{head}", self.web_url()?)),
             _ => None,
         }
     }
 
-    pub fn pull_request_url(&self, pr: Option<u32>) -> Option<String> {
-        match self.git.hostname.as_str() {
-            "github.com" => match pr {
-                Some(pr) => Some(format!(
-                    "https://github.com/{}/{}/pull/{}",
-                    self.git.owner, self.git.name, pr
-                )),
-                None => Some(format!(
-                    "https://github.com/{}/{}/pull",
-                    self.git.owner, self.git.name
-                )),
-            },
+    pub fn file_commits_url(&self, branch: &str, path: &str) -> Option<String> {
+        match self.provider? {
+            Provider::GitHub => Some(format!("{}/commits/{branch}/{path}", self.web_url()?)),
+            Provider::GitLab => Some(format!("{}/-/commits/{branch}/{path}", self.web_url()?)),
+            Provider::Gitea => Some(format!(
+                "{}/commits/branch/{branch}/{path}",
+                self.web_url()?
+            )),
+            _ => None,
+        }
+    }
+
+    /// Lists the runs of a GitHub Actions workflow. `workflow` is a file name in
+    /// `.github/workflows`, with or without the `.yml` extension.
+    pub fn workflow_url(&self, workflow: &str) -> Option<String> {
+        let file = if workflow.ends_with(".yml") || workflow.ends_with(".yaml") {
+            workflow.to_string()
+        } else {
+            format!("{workflow}.yml")
+        };
+        match self.provider? {
+            Provider::GitHub => Some(format!("{}/actions/workflows/{file}", self.web_url()?)),
+            _ => None,
+        }
+    }
+
+    /// GitHub can't tell us whether the owner is an org, so this always builds
+    /// the org URL and lets GitHub redirect for user accounts.
+    pub fn org_projects_url(&self) -> Option<String> {
+        match self.provider? {
+            Provider::GitHub => Some(format!(
+                "https://{}/orgs/{}/projects",
+                self.git.hostname, self.git.owner
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn org_teams_url(&self) -> Option<String> {
+        match self.provider? {
+            Provider::GitHub => Some(format!(
+                "https://{}/orgs/{}/teams",
+                self.git.hostname, self.git.owner
+            )),
+            _ => None,
+        }
+    }
+
+    /// Lists pull requests authored by the current user, in this repository or
+    /// across the owning organization.
+    pub fn my_pull_requests_url(&self, org: bool) -> Option<String> {
+        let host = &self.git.hostname;
+        match (self.provider?, org) {
+            (Provider::GitHub, true) => Some(format!(
+                "https://{host}/pulls?q=is:pr+org:{}+author:@me",
+                self.git.owner
+            )),
+            (Provider::GitHub, false) => {
+                Some(format!("{}/pulls?q=is:pr+author:@me", self.web_url()?))
+            }
+            // GitLab's dashboard lists the user's merge requests across all groups.
+            (Provider::GitLab, true) => Some(format!("https://{host}/dashboard/merge_requests")),
+            _ => None,
+        }
+    }
+
+    /// Lists the repository's security advisories, or opens a draft for a new one.
+    pub fn security_advisories_url(&self, new: bool) -> Option<String> {
+        let suffix = if new { "/new" } else { "" };
+        match self.provider? {
+            Provider::GitHub => Some(format!("{}/security/advisories{suffix}", self.web_url()?)),
+            _ => None,
+        }
+    }
+
+    /// Lists the releases, or shows the release for `tag`.
+    pub fn releases_url(&self, tag: Option<&str>) -> Option<String> {
+        let base = self.web_url()?;
+        match (self.provider?, tag) {
+            (Provider::GitHub | Provider::Gitea, None) => Some(format!("{base}/releases")),
+            (Provider::GitHub | Provider::Gitea, Some(tag)) => {
+                Some(format!("{base}/releases/tag/{tag}"))
+            }
+            (Provider::GitLab, None) => Some(format!("{base}/-/releases")),
+            (Provider::GitLab, Some(tag)) => Some(format!("{base}/-/releases/{tag}")),
+            _ => None,
+        }
+    }
+
+    /// Lists CI runs: GitHub and Gitea Actions, or GitLab pipelines.
+    pub fn actions_url(&self) -> Option<String> {
+        match self.provider? {
+            Provider::GitHub | Provider::Gitea => Some(format!("{}/actions", self.web_url()?)),
+            Provider::GitLab => Some(format!("{}/-/pipelines", self.web_url()?)),
+            _ => None,
+        }
+    }
+
+    pub fn latest_release_url(&self) -> Option<String> {
+        match self.provider? {
+            Provider::GitHub | Provider::Gitea => {
+                Some(format!("{}/releases/latest", self.web_url()?))
+            }
+            Provider::GitLab => Some(format!("{}/-/releases/permalink/latest", self.web_url()?)),
             _ => None,
         }
     }
 }
 
+/// Where repositories are cloned inside a root, set by the `layout` config.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// `root/github.com/zhanba/pji`
+    #[default]
+    HostUserRepo,
+    /// `root/zhanba/pji`
+    UserRepo,
+    /// `root/pji`
+    Flat,
+}
+
+impl Layout {
+    /// How many directories a repository sits below its root.
+    pub(crate) fn depth(self) -> usize {
+        match self {
+            Self::HostUserRepo => 3,
+            Self::UserRepo => 2,
+            Self::Flat => 1,
+        }
+    }
+
+    /// The directory of a repository relative to its root.
+    pub(crate) fn relative_dir(self, hostname: &str, owner: &str, name: &str) -> PathBuf {
+        match self {
+            Self::HostUserRepo => Path::new(hostname).join(owner).join(name),
+            Self::UserRepo => Path::new(owner).join(name),
+            Self::Flat => PathBuf::from(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListColumn {
+    Dir,
+    Uri,
+    Host,
+    User,
+    Repo,
+    Root,
+    Created,
+    Opened,
+}
+
+impl ListColumn {
+    pub fn header(&self) -> &'static str {
+        match self {
+            Self::Dir => "dir",
+            Self::Uri => "uri",
+            Self::Host => "host",
+            Self::User => "user",
+            Self::Repo => "repo",
+            Self::Root => "root",
+            Self::Created => "created",
+            Self::Opened => "opened",
+        }
+    }
+}
+
+/// How `Pji::repositories_by` orders repositories.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepositoryOrder {
+    /// Most recently opened first.
+    #[default]
+    Recent,
+    /// By repository name, then owner and host, ignoring case.
+    Name,
+    /// Most recently added first.
+    Created,
+    /// Highest `Repository::frecency` first, then most recently opened.
+    Frecency,
+}
+
+impl RepositoryOrder {
+    pub fn compare(self, a: &Repository, b: &Repository, now: DateTime<Utc>) -> Ordering {
+        let recent = || b.last_opened_at.cmp(&a.last_opened_at);
+        match self {
+            Self::Recent => recent(),
+            Self::Name => {
+                let key = |repo: &Repository| {
+                    [&repo.git.name, &repo.git.owner, &repo.git.hostname].map(|s| s.to_lowercase())
+                };
+                key(a).cmp(&key(b))
+            }
+            Self::Created => b.created_at.cmp(&a.created_at),
+            Self::Frecency => b
+                .frecency(now)
+                .total_cmp(&a.frecency(now))
+                .then_with(recent),
+        }
+    }
+
+    pub fn sort(self, repos: &mut [Repository], now: DateTime<Utc>) {
+        repos.sort_by(|a, b| self.compare(a, b, now));
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanReport {
     pub added: Vec<Repository>,
@@ -99,12 +508,87 @@ pub struct ScanReport {
     pub issues: Vec<ScanIssue>,
 }
 
+/// What [`Pji::scan_root`] found under one root. Unlike
+/// [`ScanReport::added`], `discovered` includes repositories already tracked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootScan {
+    pub discovered: Vec<Repository>,
+    pub invalid_paths: Vec<PathBuf>,
+    pub issues: Vec<ScanIssue>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanIssue {
     pub path: PathBuf,
     pub message: String,
 }
 
+/// Working tree and upstream state of a tracked repository, from [`Pji::status`].
+#[derive(Debug, Clone)]
+pub struct RepositoryStatus {
+    pub repository: Repository,
+    /// Whether the repository directory is gone from disk.
+    pub missing: bool,
+    /// Modified, staged, and untracked files; `None` if git couldn't tell.
+    pub changed_files: Option<usize>,
+    /// Commits `(ahead, behind)` the upstream; `None` without an upstream.
+    pub ahead_behind: Option<(usize, usize)>,
+    /// Why git couldn't read this repository's state, e.g. a timeout.
+    pub error: Option<String>,
+}
+
+/// What [`Pji::sync`] did with a tracked repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// The directory was missing and has been cloned again.
+    Cloned,
+    /// The directory exists and `git pull --ff-only` succeeded.
+    Pulled,
+    /// The directory exists and pulling wasn't requested.
+    Present,
+    /// The host asked for credentials, which sync never prompts for.
+    AuthRequired(String),
+    Failed(String),
+}
+
+/// What [`Pji::import_repository`] did with an exported repository.
+#[derive(Debug, Clone)]
+pub enum ImportOutcome {
+    /// The repository was already tracked in the root; nothing changed.
+    AlreadyTracked,
+    /// A clone already sat in the repository's directory and is now tracked.
+    TrackedExisting(Repository),
+    Cloned(Repository),
+}
+
+/// A tracked repository and what [`Pji::sync`] did with it.
+#[derive(Debug, Clone)]
+pub struct RepositorySync {
+    pub repository: Repository,
+    pub outcome: SyncOutcome,
+}
+
+/// A config or metadata file that couldn't be parsed and was moved aside by
+/// [`Pji::load_or_recover`].
+#[derive(Debug, Clone)]
+pub struct RecoveredFile {
+    pub path: PathBuf,
+    pub backup: PathBuf,
+    pub message: String,
+}
+
+/// Extra `git clone` arguments for [`Pji::clone_repository_with`].
+#[derive(Debug, Clone, Default)]
+pub struct CloneOptions {
+    /// Create a shallow clone with this many commits of history.
+    pub depth: Option<u32>,
+    /// Check out this branch instead of the remote's default branch.
+    pub branch: Option<String>,
+    /// Also clone submodules. The `clone_recurse_submodules` config turns this
+    /// on for every clone.
+    pub recurse_submodules: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct AddWorktreeRequest {
     pub repo_dir: PathBuf,
@@ -128,13 +612,58 @@ pub type WorktreeList = worktree::WorktreeList;
 pub struct Pji {
     config: PjiConfig,
     metadata: PjiMetadata,
+    git: GitRunner,
 }
 
 impl Pji {
     pub fn load() -> Result<Self, PjiError> {
         let config = PjiConfig::try_load().map_err(PjiError::Config)?;
-        let metadata = PjiMetadata::try_load().map_err(PjiError::Metadata)?;
-        Ok(Self { config, metadata })
+        let metadata = PjiMetadata::try_load()?;
+        let mut pji = Self::new(config, metadata);
+        if std::env::var_os(GIT_LOG_ENV).is_some_and(|value| !value.is_empty()) {
+            pji.enable_git_log()?;
+        }
+        Ok(pji)
+    }
+
+    /// Like [`Pji::load`], but a config or metadata file that isn't valid TOML
+    /// is renamed to `<file>.bak` and replaced by defaults instead of failing.
+    pub fn load_or_recover() -> Result<(Self, Vec<RecoveredFile>), PjiError> {
+        let mut recovered = Vec::new();
+        let (config, backup) = PjiConfig::load_or_back_up().map_err(PjiError::Config)?;
+        if let Some((backup, message)) = backup {
+            recovered.push(RecoveredFile {
+                path: Self::config_file_path()?,
+                backup,
+                message,
+            });
+        }
+        let (metadata, backup) = PjiMetadata::load_or_back_up()?;
+        if let Some((backup, message)) = backup {
+            recovered.push(RecoveredFile {
+                path: Self::metadata_file_path()?,
+                backup,
+                message,
+            });
+        }
+
+        let mut pji = Self::new(config, metadata);
+        if std::env::var_os(GIT_LOG_ENV).is_some_and(|value| !value.is_empty()) {
+            pji.enable_git_log()?;
+        }
+        Ok((pji, recovered))
+    }
+
+    fn new(config: PjiConfig, metadata: PjiMetadata) -> Self {
+        let git = GitRunner::new(
+            Duration::from_secs(config.git_timeout.unwrap_or(DEFAULT_GIT_TIMEOUT_SECS)),
+            Duration::from_secs(config.clone_timeout.unwrap_or(DEFAULT_CLONE_TIMEOUT_SECS)),
+        );
+        Self {
+            config,
+            metadata,
+            git,
+        }
     }
 
     pub fn config_file_path() -> Result<PathBuf, PjiError> {
@@ -145,6 +674,20 @@ impl Pji {
         PjiMetadata::get_metadata_file_path().map_err(PjiError::Metadata)
     }
 
+    pub fn git_log_file_path() -> Result<PathBuf, PjiError> {
+        Ok(Self::metadata_file_path()?.with_file_name(APP_GIT_LOG_NAME))
+    }
+
+    /// Appends every git command pji runs to [`Pji::git_log_file_path`].
+    pub fn enable_git_log(&mut self) -> Result<PathBuf, PjiError> {
+        let path = Self::git_log_file_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        self.git.set_log_file(Some(path.clone()));
+        Ok(path)
+    }
+
     pub fn default_root() -> Result<PathBuf, PjiError> {
         PjiConfig::get_default_root()
     }
@@ -160,23 +703,124 @@ impl Pji {
 
     pub fn add_root(&mut self, root: impl Into<PathBuf>) {
         let root = root.into();
-        if !self.config.roots.contains(&root) {
+        if self.config.configured_root(&root).is_none() {
             self.config.roots.push(root);
         }
     }
 
+    /// Removes `root` from the config. Repositories tracked under it stay in
+    /// the metadata; without `force` the root is kept if there are any.
+    pub fn remove_root(&mut self, root: impl AsRef<Path>, force: bool) -> Result<(), PjiError> {
+        let root = self
+            .config
+            .configured_root(root.as_ref())
+            .cloned()
+            .ok_or_else(|| PjiError::RootNotConfigured(root.as_ref().to_path_buf()))?;
+        let count = self.repository_count_in_root(&root);
+        if count > 0 && !force {
+            return Err(PjiError::RootHasRepositories { root, count });
+        }
+        self.config.roots.retain(|configured| *configured != root);
+        Ok(())
+    }
+
+    /// Returns how many tracked repositories were registered under `root`.
+    pub fn repository_count_in_root(&self, root: impl AsRef<Path>) -> usize {
+        let root = root.as_ref();
+        self.metadata
+            .repos
+            .iter()
+            .filter(|repo| repo.root == root)
+            .count()
+    }
+
+    /// Returns the configured root that contains `path`, following symlinks.
+    pub fn root_containing(&self, path: impl AsRef<Path>) -> Option<&Path> {
+        self.config
+            .is_under_root(path.as_ref())
+            .map(PathBuf::as_path)
+    }
+
+    /// Returns the configured root that `path` names, following symlinks.
+    pub fn configured_root(&self, path: impl AsRef<Path>) -> Option<&Path> {
+        self.config
+            .configured_root(path.as_ref())
+            .map(PathBuf::as_path)
+    }
+
+    /// Moves `root` to the front of the roots so it is preselected when a
+    /// command asks for a root.
+    pub fn set_default_root(&mut self, root: impl AsRef<Path>) -> Result<(), PjiError> {
+        let root = self
+            .config
+            .configured_root(root.as_ref())
+            .cloned()
+            .ok_or_else(|| PjiError::RootNotConfigured(root.as_ref().to_path_buf()))?;
+        self.config.roots.retain(|configured| *configured != root);
+        self.config.roots.insert(0, root);
+        Ok(())
+    }
+
+    /// Resolves the provider of `repo`: its own override first, then the
+    /// `providers` config, then the well-known public hosts. `host_aliases`
+    /// map SSH host aliases to the host used for web URLs.
+    fn with_configured_provider(&self, mut repo: Repository) -> Repository {
+        repo.web_host = self.config.host_aliases.get(&repo.git.hostname).cloned();
+        let host = repo.web_host.as_ref().unwrap_or(&repo.git.hostname);
+        repo.provider = repo
+            .provider_override
+            .or_else(|| self.config.providers.get(host).copied())
+            .or_else(|| Provider::from_hostname(host));
+        repo
+    }
+
+    pub fn list_columns(&self) -> &[ListColumn] {
+        &self.config.list_columns
+    }
+
+    pub fn browser(&self) -> Option<&str> {
+        self.config.browser.as_deref()
+    }
+
+    /// Arguments of the command a bare `pji` runs, if configured.
+    pub fn default_command(&self) -> Option<&str> {
+        self.config.default_command.as_deref()
+    }
+
+    pub fn tui_command(&self) -> &str {
+        self.config.tui.as_deref().unwrap_or(DEFAULT_TUI_COMMAND)
+    }
+
+    pub fn editor(&self) -> Option<&str> {
+        self.config.editor.as_deref()
+    }
+
+    pub fn terminal_command(&self) -> Option<&str> {
+        self.config.terminal.as_deref()
+    }
+
     pub fn repositories(&self) -> Vec<Repository> {
         self.metadata
             .repos
             .iter()
             .cloned()
-            .map(Repository::from)
+            .map(|repo| self.with_configured_provider(repo.into()))
             .collect()
     }
 
     pub fn repositories_by_last_opened(&self) -> Vec<Repository> {
+        self.repositories_by(RepositoryOrder::Recent)
+    }
+
+    /// Repositories sorted by `Repository::frecency`, most relevant first.
+    /// Ties keep the most recently opened repository first.
+    pub fn repositories_by_frecency(&self) -> Vec<Repository> {
+        self.repositories_by(RepositoryOrder::Frecency)
+    }
+
+    pub fn repositories_by(&self, order: RepositoryOrder) -> Vec<Repository> {
         let mut repos = self.repositories();
-        repos.sort_by(|a, b| b.last_opened_at.cmp(&a.last_opened_at));
+        order.sort(&mut repos, Utc::now());
         repos
     }
 
@@ -184,11 +828,14 @@ impl Pji {
         GitUrl::parse(url)
     }
 
-    pub fn repository_path(root: impl AsRef<Path>, git: &GitUrl) -> PathBuf {
-        root.as_ref()
-            .join(&git.hostname)
-            .join(&git.owner)
-            .join(&git.name)
+    /// The directory a repository is cloned to in `root`, following the
+    /// configured [`Layout`].
+    pub fn repository_path(&self, root: impl AsRef<Path>, git: &GitUrl) -> PathBuf {
+        root.as_ref().join(
+            self.config
+                .layout
+                .relative_dir(&git.hostname, &git.owner, &git.name),
+        )
     }
 
     pub fn is_repository_registered(
@@ -196,8 +843,8 @@ impl Pji {
         url: &str,
         root: impl AsRef<Path>,
     ) -> Result<bool, PjiError> {
-        let repo = PjiRepo::try_new(url, root.as_ref())?;
-        Ok(self.metadata.has_repo(&repo))
+        let repo = PjiRepo::try_new(url, root.as_ref(), self.config.layout)?;
+        self.metadata.has_repo(&repo)
     }
 
     pub fn unregister_repository(
@@ -205,8 +852,8 @@ impl Pji {
         url: &str,
         root: impl AsRef<Path>,
     ) -> Result<(), PjiError> {
-        let repo = PjiRepo::try_new(url, root.as_ref())?;
-        if !self.metadata.has_repo(&repo) {
+        let repo = PjiRepo::try_new(url, root.as_ref(), self.config.layout)?;
+        if !self.metadata.has_repo(&repo)? {
             return Err(PjiError::RepositoryNotRegistered(repo.dir));
         }
 
@@ -214,84 +861,538 @@ impl Pji {
         Ok(())
     }
 
-    pub fn clone_repository(
-        &mut self,
-        url: &str,
-        root: impl AsRef<Path>,
-    ) -> Result<Repository, PjiError> {
-        let repo = PjiRepo::try_new(url, root.as_ref())?;
-        if self.metadata.has_repo(&repo) {
-            return Err(PjiError::RepositoryAlreadyRegistered(repo.dir));
-        }
-
-        std::fs::create_dir_all(&repo.dir)?;
-        let output = Command::new("git")
-            .args(["clone", &repo.git_uri.uri])
-            .arg(&repo.dir)
-            .output()?;
-
-        if !output.status.success() {
-            return Err(PjiError::GitCommand {
-                command: format!("git clone {} {}", repo.git_uri.uri, repo.dir.display()),
-                stderr: command_error_output(&output),
-            });
+    pub fn unregister_repository_dir(&mut self, dir: impl AsRef<Path>) -> Result<(), PjiError> {
+        let dir = dir.as_ref();
+        let count = self.metadata.repos.len();
+        self.metadata.repos.retain(|repo| repo.dir != dir);
+        if self.metadata.repos.len() == count {
+            return Err(PjiError::RepositoryNotRegistered(dir.to_path_buf()));
         }
-
-        self.metadata.add_repo(&repo);
-        Ok(repo.into())
+        Ok(())
     }
 
-    pub fn find_repositories(&self, query: &str) -> Vec<Repository> {
-        let query = query.to_lowercase();
+    /// Tracked repositories whose directory no longer exists.
+    pub fn missing_repositories(&self) -> Vec<Repository> {
         self.repositories()
             .into_iter()
-            .filter(|repo| {
-                query.is_empty()
-                    || repo.git.owner.to_lowercase().contains(&query)
-                    || repo.git.name.to_lowercase().contains(&query)
-                    || repo.git.original.to_lowercase().contains(&query)
-                    || repo.dir.to_string_lossy().to_lowercase().contains(&query)
-            })
+            .filter(|repo| !repo.dir.exists())
             .collect()
     }
 
-    pub fn scan(&mut self) -> Result<ScanReport, PjiError> {
-        self.metadata.deduplicate();
-
-        let mut added = Vec::new();
-        let mut invalid_paths = Vec::new();
-        let mut issues = Vec::new();
-        for root in self.config.roots.clone() {
-            let scan = scan_root(&root)?;
-            invalid_paths.extend(scan.invalid_paths);
-            issues.extend(scan.issues);
-
-            for repo in scan.added {
-                let pji_repo = PjiRepo::from(repo.clone());
-                if !self.metadata.has_repo(&pji_repo) {
-                    self.metadata.repos.push(pji_repo);
-                    added.push(repo);
-                }
-            }
-        }
-
-        Ok(ScanReport {
-            added,
-            invalid_paths,
-            issues,
-        })
-    }
-
-    pub fn resolve_repository(&self, cwd: impl AsRef<Path>) -> Option<Repository> {
-        let cwd = cwd.as_ref();
-        let resolved_dir = resolve_git_dir(cwd).unwrap_or_else(|| cwd.to_path_buf());
-
-        self.metadata
+    /// Stops tracking every repository whose directory no longer exists and
+    /// returns them. Call [`Pji::save`] to persist the change.
+    pub fn prune_missing(&mut self) -> Vec<Repository> {
+        let missing = self
+            .metadata
             .repos
             .iter()
-            .find(|repo| resolved_dir.starts_with(&repo.dir))
+            .filter(|repo| !repo.dir.exists())
             .cloned()
-            .map(Repository::from)
+            .collect::<Vec<_>>();
+        for repo in &missing {
+            self.metadata.remove_repo(repo);
+        }
+        missing
+            .into_iter()
+            .map(|repo| self.with_configured_provider(repo.into()))
+            .collect()
+    }
+
+    /// Moves a tracked repository into `to_root`, keeping its path relative to
+    /// its current root, and updates its `root` and `dir`. Roots on different
+    /// filesystems are handled by copying and deleting the original. Call
+    /// [`Pji::save`] to persist the change.
+    pub fn move_repository(
+        &mut self,
+        dir: impl AsRef<Path>,
+        to_root: impl AsRef<Path>,
+    ) -> Result<Repository, PjiError> {
+        let dir = dir.as_ref();
+        let to_root = self
+            .config
+            .configured_root(to_root.as_ref())
+            .cloned()
+            .ok_or_else(|| PjiError::RootNotConfigured(to_root.as_ref().to_path_buf()))?;
+        let repo = self
+            .metadata
+            .repos
+            .iter_mut()
+            .find(|repo| repo.dir == dir)
+            .ok_or_else(|| PjiError::RepositoryNotRegistered(dir.to_path_buf()))?;
+
+        let relative = match repo.dir.strip_prefix(&repo.root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => self.config.layout.relative_dir(
+                &repo.git_uri.hostname,
+                &repo.git_uri.user,
+                &repo.git_uri.repo,
+            ),
+        };
+        let target = to_root.join(relative);
+        if target != repo.dir {
+            if target.exists() {
+                return Err(PjiError::DestinationExists(target));
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            move_dir(&repo.dir, &target)?;
+        }
+
+        repo.dir = target;
+        repo.root = to_root;
+        let repo = repo.clone();
+        Ok(self.with_configured_provider(repo.into()))
+    }
+
+    /// Returns the URL `pji add` should clone. With `auto_protocol` enabled in the
+    /// config, GitHub and GitLab URLs are switched to SSH when SSH credentials are
+    /// available and to HTTPS otherwise; without it `url` is returned unchanged.
+    pub fn preferred_clone_url(&self, url: &str) -> Result<String, PjiError> {
+        let git_uri = parse_git_url(url)
+            .ok_or_else(|| PjiError::InvalidGitUrl(redact_url_credentials(url)))?;
+        if !self.config.auto_protocol {
+            return Ok(git_uri.uri);
+        }
+
+        let provider = self
+            .config
+            .providers
+            .get(&git_uri.hostname)
+            .copied()
+            .or_else(|| Provider::from_hostname(&git_uri.hostname));
+        Ok(preferred_clone_url(
+            &git_uri,
+            provider,
+            has_ssh_credentials(),
+        ))
+    }
+
+    pub fn clone_repository(
+        &mut self,
+        url: &str,
+        root: impl AsRef<Path>,
+    ) -> Result<Repository, PjiError> {
+        self.clone_repository_with(url, root, &CloneOptions::default())
+    }
+
+    pub fn clone_repository_with(
+        &mut self,
+        url: &str,
+        root: impl AsRef<Path>,
+        options: &CloneOptions,
+    ) -> Result<Repository, PjiError> {
+        let mut repo = PjiRepo::try_new(url, root.as_ref(), self.config.layout)?;
+        if self.metadata.has_repo(&repo)? {
+            return Err(PjiError::RepositoryAlreadyRegistered(repo.dir));
+        }
+
+        let options = CloneOptions {
+            recurse_submodules: options.recurse_submodules || self.config.clone_recurse_submodules,
+            ..options.clone()
+        };
+        clone_repo(&self.git, &repo.git_uri.uri, &repo.dir, &options, true)?;
+
+        repo.default_branch = default_branch(&self.git, &repo.dir).ok().flatten();
+        self.metadata.add_repo(&repo);
+        Ok(self.with_configured_provider(repo.into()))
+    }
+
+    /// Tracks a clone of `url` that already sits in its pji directory, e.g. one
+    /// made by hand before `pji add`. Returns `None` when there is no git
+    /// checkout there, and an error when the checkout's origin is another
+    /// repository.
+    pub fn track_existing_clone(
+        &mut self,
+        url: &str,
+        root: impl AsRef<Path>,
+    ) -> Result<Option<Repository>, PjiError> {
+        let mut repo = PjiRepo::try_new(url, root.as_ref(), self.config.layout)?;
+        if self.metadata.has_repo(&repo)? {
+            return Err(PjiError::RepositoryAlreadyRegistered(repo.dir));
+        }
+        if !repo.dir.join(".git").exists() {
+            return Ok(None);
+        }
+
+        let origin = try_get_repo_from_dir(&self.git, &repo.dir)?;
+        match origin.as_deref().and_then(parse_git_url) {
+            Some(live) if live.same_repository(&repo.git_uri) => {}
+            _ => {
+                return Err(PjiError::DirectoryHasOtherOrigin {
+                    dir: repo.dir,
+                    origin: origin.unwrap_or_else(|| "none".to_string()),
+                })
+            }
+        }
+        repo.default_branch = default_branch(&self.git, &repo.dir).ok().flatten();
+        self.metadata.add_repo(&repo);
+        Ok(Some(self.with_configured_provider(repo.into())))
+    }
+
+    /// Tracks `repo`, read by [`Pji::parse_export`], under `root`: a clone
+    /// already in its directory is tracked as is, and otherwise it is cloned.
+    /// Its tags are carried over. Importing the same repository again does
+    /// nothing. Call [`Pji::save`] to persist the change.
+    pub fn import_repository(
+        &mut self,
+        repo: &Repository,
+        root: impl AsRef<Path>,
+        options: &CloneOptions,
+    ) -> Result<ImportOutcome, PjiError> {
+        let (url, root) = (&repo.git.original, root.as_ref());
+        if self.is_repository_registered(url, root)? {
+            return Ok(ImportOutcome::AlreadyTracked);
+        }
+        let existing = self.track_existing_clone(url, root)?;
+        let tracked_existing = existing.is_some();
+        let mut imported = match existing {
+            Some(tracked) => tracked,
+            None => self.clone_repository_with(url, root, options)?,
+        };
+        let pji_repo = self.tracked_repo_mut(&imported.dir)?;
+        pji_repo.add_tags(repo.tags.iter().map(String::as_str));
+        imported.tags = pji_repo.tags.clone();
+        Ok(if tracked_existing {
+            ImportOutcome::TrackedExisting(imported)
+        } else {
+            ImportOutcome::Cloned(imported)
+        })
+    }
+
+    /// Serializes the tracked repositories as pretty JSON for `pji import` on
+    /// another machine.
+    pub fn export_json(&self) -> Result<String, PjiError> {
+        serde_json::to_string_pretty(&self.metadata).map_err(PjiError::InvalidExport)
+    }
+
+    /// Reads the repositories from JSON written by [`Pji::export_json`].
+    /// Their `dir` and `root` are those of the exporting machine.
+    pub fn parse_export(json: &str) -> Result<Vec<Repository>, PjiError> {
+        let mut metadata: PjiMetadata =
+            serde_json::from_str(json).map_err(PjiError::InvalidExport)?;
+        metadata.migrate()?;
+        Ok(metadata.repos.into_iter().map(Repository::from).collect())
+    }
+
+    pub fn find_repositories(&self, query: &str) -> Vec<Repository> {
+        let query = query.to_lowercase();
+        self.repositories()
+            .into_iter()
+            .filter(|repo| {
+                query.is_empty()
+                    || repo.git.owner.to_lowercase().contains(&query)
+                    || repo.git.name.to_lowercase().contains(&query)
+                    || repo.git.original.to_lowercase().contains(&query)
+                    || repo.dir.to_string_lossy().to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+
+    pub fn scan(&mut self) -> Result<ScanReport, PjiError> {
+        self.scan_roots(false)
+    }
+
+    /// Like [`Pji::scan`], but searches each root recursively and registers
+    /// every repository with an origin remote where it is, even outside the
+    /// `host/user/repo` layout.
+    pub fn deep_scan(&mut self) -> Result<ScanReport, PjiError> {
+        self.scan_roots(true)
+    }
+
+    /// Discovers the repositories in the `host/user/repo` layout under
+    /// `root` without registering them.
+    pub fn scan_root(&self, root: impl AsRef<Path>) -> Result<RootScan, PjiError> {
+        self.discover(root.as_ref(), false)
+    }
+
+    /// Like [`Pji::scan_root`], but searches `root` recursively like
+    /// [`Pji::deep_scan`].
+    pub fn deep_scan_root(&self, root: impl AsRef<Path>) -> Result<RootScan, PjiError> {
+        self.discover(root.as_ref(), true)
+    }
+
+    fn discover(&self, root: &Path, deep: bool) -> Result<RootScan, PjiError> {
+        let mut report = scan_root(&self.git, root, self.config.layout, deep)?;
+        report.discovered = report
+            .discovered
+            .into_iter()
+            .map(|repo| self.with_configured_provider(repo))
+            .collect();
+        Ok(report)
+    }
+
+    fn scan_roots(&mut self, deep: bool) -> Result<ScanReport, PjiError> {
+        self.metadata.deduplicate();
+
+        let mut added = Vec::new();
+        let mut invalid_paths = Vec::new();
+        let mut issues = Vec::new();
+        for root in self.config.roots.clone() {
+            let scan = self.discover(&root, deep)?;
+            invalid_paths.extend(scan.invalid_paths);
+            issues.extend(scan.issues);
+
+            for repo in scan.discovered {
+                let pji_repo = PjiRepo::from(repo.clone());
+                match self.metadata.has_repo(&pji_repo) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        self.metadata.repos.push(pji_repo);
+                        added.push(repo);
+                    }
+                    Err(err) => issues.push(ScanIssue {
+                        path: repo.dir,
+                        message: err.to_string(),
+                    }),
+                }
+            }
+        }
+
+        // Refresh every tracked repository, so a remote that renamed its
+        // default branch isn't remembered by the old name.
+        let dirs = self
+            .metadata
+            .repos
+            .iter()
+            .map(|repo| repo.dir.clone())
+            .collect::<Vec<_>>();
+        let branches = in_parallel(&dirs, |dir| default_branch(&self.git, dir).ok().flatten());
+        for (tracked, branch) in self.metadata.repos.iter_mut().zip(branches) {
+            if branch.is_some() {
+                tracked.default_branch = branch;
+            }
+        }
+        for repo in &mut added {
+            if let Ok(tracked) = self.tracked_repo_mut(&repo.dir) {
+                repo.default_branch = tracked.default_branch.clone();
+            }
+        }
+
+        Ok(ScanReport {
+            added,
+            invalid_paths,
+            issues,
+        })
+    }
+
+    /// Checks every tracked repository against the disk: the directory must be
+    /// a git checkout whose `remote.origin.url` is the repository pji tracks.
+    /// Returns one issue per inconsistent repository.
+    pub fn verify(&self) -> Result<Vec<ScanIssue>, PjiError> {
+        let mut issues = Vec::new();
+        let mut checkouts = Vec::new();
+        for repo in &self.metadata.repos {
+            let message = if !repo.dir.is_dir() {
+                "directory does not exist"
+            } else if !repo.dir.join(".git").exists() {
+                "directory is not a git repository"
+            } else {
+                checkouts.push(repo);
+                continue;
+            };
+            issues.push(ScanIssue {
+                path: repo.dir.clone(),
+                message: message.to_string(),
+            });
+        }
+
+        let dirs = checkouts
+            .iter()
+            .map(|repo| repo.dir.clone())
+            .collect::<Vec<_>>();
+        for (repo, remote_url) in checkouts.into_iter().zip(remote_urls(&self.git, &dirs)) {
+            let message = match remote_url {
+                Ok(Some(url)) => match parse_git_url(&url) {
+                    Some(live) if live.same_repository(&repo.git_uri) => continue,
+                    Some(_) => format!(
+                        "remote.origin.url is '{}' but pji tracks '{}'",
+                        redact_url_credentials(&url),
+                        redact_url_credentials(&repo.git_uri.uri)
+                    ),
+                    None => format!(
+                        "remote.origin.url '{}' is not a valid git url",
+                        redact_url_credentials(&url)
+                    ),
+                },
+                Ok(None) => "remote.origin.url not found".to_string(),
+                Err(PjiError::Io(err)) => return Err(PjiError::Io(err)),
+                Err(err) => err.to_string(),
+            };
+            issues.push(ScanIssue {
+                path: repo.dir.clone(),
+                message,
+            });
+        }
+        Ok(issues)
+    }
+
+    /// Directories tracked for more than one repository, which older pji
+    /// versions could record with the shorter layouts. Both entries are kept
+    /// until one is removed.
+    pub fn directory_conflicts(&self) -> Vec<ScanIssue> {
+        self.metadata
+            .directory_conflicts()
+            .into_iter()
+            .map(|(dir, uris)| ScanIssue {
+                path: dir,
+                message: format!("tracked for several repositories: {}", uris.join(", ")),
+            })
+            .collect()
+    }
+
+    /// Reads the working tree and upstream state of every tracked repository.
+    /// Each repository costs two git processes, so they are split across
+    /// worker threads. A repository git fails on gets an `error` instead of
+    /// failing the whole report.
+    pub fn status(&self) -> Vec<RepositoryStatus> {
+        let git = &self.git;
+        in_parallel(&self.repositories(), |repo| {
+            if !repo.dir.is_dir() {
+                return RepositoryStatus {
+                    repository: repo.clone(),
+                    missing: true,
+                    changed_files: None,
+                    ahead_behind: None,
+                    error: None,
+                };
+            }
+            let changed_files = changed_file_count(git, &repo.dir);
+            let ahead_behind = ahead_behind(git, &repo.dir);
+            let error = match (&changed_files, &ahead_behind) {
+                (Err(err), _) | (_, Err(err)) => Some(err.to_string()),
+                _ => None,
+            };
+            RepositoryStatus {
+                repository: repo.clone(),
+                missing: false,
+                changed_files: changed_files.ok().flatten(),
+                ahead_behind: ahead_behind.ok().flatten(),
+                error,
+            }
+        })
+    }
+
+    /// Clones every tracked repository whose directory is gone back into place,
+    /// and with `pull` also fast-forwards the ones that exist. Neither git nor
+    /// ssh prompts here, so hosts that need credentials, a key passphrase, or
+    /// an unknown host key confirmed are reported as
+    /// [`SyncOutcome::AuthRequired`]. Metadata is left unchanged.
+    pub fn sync(&self, pull: bool) -> Vec<RepositorySync> {
+        let git = &self.git;
+        let options = CloneOptions {
+            recurse_submodules: self.config.clone_recurse_submodules,
+            ..CloneOptions::default()
+        };
+        in_parallel(&self.repositories(), |repo| {
+            let outcome = if !repo.dir.exists() {
+                sync_clone(git, repo, &options)
+            } else if pull {
+                sync_pull(git, &repo.dir)
+            } else {
+                SyncOutcome::Present
+            };
+            RepositorySync {
+                repository: repo.clone(),
+                outcome,
+            }
+        })
+    }
+
+    /// Sets the last opened time of every repository to its latest commit date,
+    /// so freshly scanned repositories don't all share the scan time. Returns the
+    /// number of repositories updated.
+    pub fn update_open_times_from_git(&mut self) -> Result<usize, PjiError> {
+        let mut updated = 0;
+        for repo in &mut self.metadata.repos {
+            if !repo.dir.is_dir() {
+                continue;
+            }
+            if let Some(time) = last_commit_time(&self.git, &repo.dir)? {
+                repo.last_open_time = time;
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+
+    pub fn resolve_repository(&self, cwd: impl AsRef<Path>) -> Option<Repository> {
+        let cwd = cwd.as_ref();
+        let resolved_dir = resolve_git_dir(cwd).unwrap_or_else(|| cwd.to_path_buf());
+
+        // Either side may be reached through a symlink, e.g. `/var` and
+        // `/private/var` on macOS, so real paths are compared too. Git also
+        // records a linked worktree's main repository by its real path.
+        let real_dir = resolved_dir.canonicalize().ok();
+        let matches = self
+            .metadata
+            .repos
+            .iter()
+            .filter_map(|repo| {
+                if resolved_dir.starts_with(&repo.dir) {
+                    return Some((repo, repo.dir.components().count()));
+                }
+                let dir = repo.dir.canonicalize().ok()?;
+                real_dir
+                    .as_ref()
+                    .is_some_and(|real_dir| real_dir.starts_with(&dir))
+                    .then(|| (repo, dir.components().count()))
+            })
+            .collect::<Vec<_>>();
+        // In nested repositories the innermost one is the checkout `cwd` is in.
+        let depth = matches.iter().map(|(_, depth)| *depth).max();
+        let candidates = matches
+            .into_iter()
+            .filter(|(_, repo_depth)| Some(*repo_depth) == depth)
+            .map(|(repo, _)| repo)
+            .collect::<Vec<_>>();
+        let repo = if candidates.len() > 1 {
+            // Stale entries can share a directory; the live origin remote tells
+            // which one the checkout actually is.
+            let live = try_get_repo_from_dir(&self.git, &resolved_dir)
+                .ok()
+                .flatten()
+                .and_then(|url| parse_git_url(&url));
+            live.and_then(|live| {
+                candidates
+                    .iter()
+                    .find(|repo| repo.git_uri.same_repository(&live))
+            })
+            .or(candidates.first())
+            .copied()
+        } else {
+            candidates.first().copied()
+        };
+        repo.cloned()
+            .map(|repo| self.with_configured_provider(repo.into()))
+    }
+
+    /// Like `resolve_repository`, but with `git` and `provider` taken from the
+    /// remote the current branch tracks, such as a fork. Falls back to the
+    /// registered `origin` URL when the branch has no upstream.
+    pub fn resolve_upstream_repository(
+        &self,
+        cwd: impl AsRef<Path>,
+    ) -> Result<Option<Repository>, PjiError> {
+        let cwd = cwd.as_ref();
+        let Some(repo) = self.resolve_repository(cwd) else {
+            return Ok(None);
+        };
+        Ok(Some(match upstream_remote_url(&self.git, cwd)? {
+            Some(url) => self.with_remote_url(repo, &url),
+            None => repo,
+        }))
+    }
+
+    fn with_remote_url(&self, mut repo: Repository, url: &str) -> Repository {
+        let Some(git_uri) = parse_git_url(url) else {
+            return repo;
+        };
+        if git_uri.hostname != repo.git.hostname {
+            repo.provider_override = None;
+        }
+        repo.git = git_uri.into();
+        self.with_configured_provider(repo)
     }
 
     pub fn resolve_git_dir(cwd: impl AsRef<Path>) -> Option<PathBuf> {
@@ -312,8 +1413,61 @@ impl Pji {
         true
     }
 
+    /// Adds `tags` to the repository tracked at `dir` and returns the ones it
+    /// didn't have. Call [`Pji::save`] to persist the change.
+    pub fn add_repository_tags(
+        &mut self,
+        dir: impl AsRef<Path>,
+        tags: &[String],
+    ) -> Result<Vec<String>, PjiError> {
+        Ok(self
+            .tracked_repo_mut(dir.as_ref())?
+            .add_tags(tags.iter().map(String::as_str)))
+    }
+
+    /// Removes `tags` from the repository tracked at `dir` and returns the ones
+    /// it had. Call [`Pji::save`] to persist the change.
+    pub fn remove_repository_tags(
+        &mut self,
+        dir: impl AsRef<Path>,
+        tags: &[String],
+    ) -> Result<Vec<String>, PjiError> {
+        Ok(self
+            .tracked_repo_mut(dir.as_ref())?
+            .remove_tags(tags.iter().map(String::as_str)))
+    }
+
+    /// Every tag in use with the number of repositories carrying it, by name.
+    pub fn tags(&self) -> BTreeMap<String, usize> {
+        let mut tags = BTreeMap::new();
+        for tag in self.metadata.repos.iter().flat_map(|repo| &repo.tags) {
+            *tags.entry(tag.clone()).or_insert(0) += 1;
+        }
+        tags
+    }
+
+    fn tracked_repo_mut(&mut self, dir: &Path) -> Result<&mut PjiRepo, PjiError> {
+        self.metadata
+            .repos
+            .iter_mut()
+            .find(|repo| repo.dir == dir)
+            .ok_or_else(|| PjiError::RepositoryNotRegistered(dir.to_path_buf()))
+    }
+
+    /// Sets the provider of the repository tracked at `dir`, overriding the
+    /// `providers` config; `None` goes back to detecting it. Call
+    /// [`Pji::save`] to persist the change.
+    pub fn set_repository_provider(
+        &mut self,
+        dir: impl AsRef<Path>,
+        provider: Option<Provider>,
+    ) -> Result<(), PjiError> {
+        self.tracked_repo_mut(dir.as_ref())?.provider = provider;
+        Ok(())
+    }
+
     pub fn list_worktrees(&self, repo_dir: impl AsRef<Path>) -> Result<WorktreeList, PjiError> {
-        list_worktrees(&repo_dir.as_ref().to_path_buf())?.ok_or_else(|| {
+        list_worktrees(&self.git, &repo_dir.as_ref().to_path_buf())?.ok_or_else(|| {
             PjiError::InvalidWorktree(format!(
                 "no worktrees found for {}",
                 repo_dir.as_ref().display()
@@ -321,15 +1475,52 @@ impl Pji {
         })
     }
 
-    pub fn default_worktree_path(repo_dir: impl AsRef<Path>, branch: &str) -> PathBuf {
-        get_default_worktree_path(repo_dir.as_ref(), branch)
+    /// Where a worktree of `branch` goes by default: under the `worktree_base`
+    /// config when set, else in a `{repo}.worktrees` sibling of `repo_dir`.
+    pub fn default_worktree_path(&self, repo_dir: impl AsRef<Path>, branch: &str) -> PathBuf {
+        let repo_dir = repo_dir.as_ref();
+        get_default_worktree_path(
+            repo_dir,
+            branch,
+            self.worktrees_dir_under_base(repo_dir).as_deref(),
+        )
+    }
+
+    /// Removes the directory holding the default worktrees of `repo_dir` if it
+    /// holds no directories. Returns whether it was removed.
+    pub fn remove_empty_worktrees_dir(&self, repo_dir: impl AsRef<Path>) -> Result<bool, PjiError> {
+        let repo_dir = repo_dir.as_ref();
+        Ok(remove_empty_worktrees_dir(
+            repo_dir,
+            self.worktrees_dir_under_base(repo_dir).as_deref(),
+        )?)
+    }
+
+    /// `{worktree_base}/{host}/{owner}/{repo}` for a tracked repository, so
+    /// forks and namesakes on other hosts never share a directory, whatever
+    /// the layout. An untracked checkout falls back to its directory name.
+    fn worktrees_dir_under_base(&self, repo_dir: &Path) -> Option<PathBuf> {
+        let base = self.config.worktree_base.as_deref()?;
+        let tracked = self.metadata.repos.iter().find(|repo| repo.dir == repo_dir);
+        Some(match tracked {
+            Some(repo) => base.join(Layout::HostUserRepo.relative_dir(
+                &repo.git_uri.hostname,
+                &repo.git_uri.user,
+                &repo.git_uri.repo,
+            )),
+            None => base.join(repo_dir.file_name().unwrap_or_default()),
+        })
     }
 
     pub fn add_worktree(&self, request: AddWorktreeRequest) -> Result<PathBuf, PjiError> {
+        let path = request
+            .path
+            .unwrap_or_else(|| self.default_worktree_path(&request.repo_dir, &request.branch));
         add_worktree(
+            &self.git,
             &request.repo_dir,
             &request.branch,
-            request.path,
+            path,
             request.create_branch,
             request.base_branch.as_deref(),
         )
@@ -344,105 +1535,437 @@ impl Pji {
     }
 
     pub fn remove_worktree(&self, request: RemoveWorktreeRequest) -> Result<(), PjiError> {
-        remove_worktree(&request.repo_dir, &request.worktree_path, request.force).map_err(
-            |stderr| PjiError::GitCommand {
-                command: format!(
-                    "git -C {} worktree remove {}",
-                    request.repo_dir.display(),
-                    request.worktree_path.display()
-                ),
-                stderr,
-            },
+        remove_worktree(
+            &self.git,
+            &request.repo_dir,
+            &request.worktree_path,
+            request.force,
         )
+        .map_err(|stderr| PjiError::GitCommand {
+            command: format!(
+                "git -C {} worktree remove {}",
+                request.repo_dir.display(),
+                request.worktree_path.display()
+            ),
+            stderr,
+        })
     }
 
-    pub fn prune_worktrees(&self, repo_dir: impl AsRef<Path>) -> Result<String, PjiError> {
-        prune_worktrees(&repo_dir.as_ref().to_path_buf()).map_err(|stderr| PjiError::GitCommand {
-            command: format!("git -C {} worktree prune -v", repo_dir.as_ref().display()),
+    /// Moves the linked worktree at `from` to `to`, which must not exist yet.
+    pub fn move_worktree(
+        &self,
+        repo_dir: impl AsRef<Path>,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> Result<(), PjiError> {
+        let (repo_dir, from, to) = (repo_dir.as_ref(), from.as_ref(), to.as_ref());
+        if to.exists() {
+            return Err(PjiError::DestinationExists(to.to_path_buf()));
+        }
+        move_worktree(&self.git, repo_dir, from, to).map_err(|stderr| PjiError::GitCommand {
+            command: format!(
+                "git -C {} worktree move {} {}",
+                repo_dir.display(),
+                from.display(),
+                to.display()
+            ),
             stderr,
         })
     }
 
-    pub fn local_branches(&self, repo_dir: impl AsRef<Path>) -> Vec<String> {
-        list_local_branches(&repo_dir.as_ref().to_path_buf())
+    /// Locks the worktree at `worktree_path`, with an optional reason shown by
+    /// [`Pji::list_worktrees`].
+    pub fn lock_worktree(
+        &self,
+        repo_dir: impl AsRef<Path>,
+        worktree_path: impl AsRef<Path>,
+        reason: Option<&str>,
+    ) -> Result<(), PjiError> {
+        let (repo_dir, worktree_path) = (repo_dir.as_ref(), worktree_path.as_ref());
+        lock_worktree(
+            &self.git,
+            &repo_dir.to_path_buf(),
+            &worktree_path.to_path_buf(),
+            reason,
+        )
+        .map_err(|stderr| PjiError::GitCommand {
+            command: format!(
+                "git -C {} worktree lock {}",
+                repo_dir.display(),
+                worktree_path.display()
+            ),
+            stderr,
+        })
+    }
+
+    pub fn unlock_worktree(
+        &self,
+        repo_dir: impl AsRef<Path>,
+        worktree_path: impl AsRef<Path>,
+    ) -> Result<(), PjiError> {
+        let (repo_dir, worktree_path) = (repo_dir.as_ref(), worktree_path.as_ref());
+        unlock_worktree(
+            &self.git,
+            &repo_dir.to_path_buf(),
+            &worktree_path.to_path_buf(),
+        )
+        .map_err(|stderr| PjiError::GitCommand {
+            command: format!(
+                "git -C {} worktree unlock {}",
+                repo_dir.display(),
+                worktree_path.display()
+            ),
+            stderr,
+        })
+    }
+
+    pub fn prune_worktrees(&self, repo_dir: impl AsRef<Path>) -> Result<String, PjiError> {
+        prune_worktrees(&self.git, &repo_dir.as_ref().to_path_buf()).map_err(|stderr| {
+            PjiError::GitCommand {
+                command: format!("git -C {} worktree prune -v", repo_dir.as_ref().display()),
+                stderr,
+            }
+        })
+    }
+
+    pub fn current_branch(&self, dir: impl AsRef<Path>) -> Result<Option<String>, PjiError> {
+        current_branch(&self.git, dir.as_ref())
+    }
+
+    /// Returns the full sha of HEAD, or `None` for an empty repository.
+    pub fn head_commit(&self, dir: impl AsRef<Path>) -> Result<Option<String>, PjiError> {
+        head_commit(&self.git, dir.as_ref())
+    }
+
+    pub fn default_branch(&self, dir: impl AsRef<Path>) -> Result<Option<String>, PjiError> {
+        default_branch(&self.git, dir.as_ref())
+    }
+
+    /// Returns the default branch recorded for the repository tracked at
+    /// `dir`, which [`Pji::scan`] refreshes. Repositories recorded before pji
+    /// kept it ask git and record the answer; call [`Pji::save`] to persist it.
+    pub fn repository_default_branch(
+        &mut self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Option<String>, PjiError> {
+        let dir = dir.as_ref();
+        if let Some(branch) = &self.tracked_repo_mut(dir)?.default_branch {
+            return Ok(Some(branch.clone()));
+        }
+        let branch = default_branch(&self.git, dir)?;
+        self.tracked_repo_mut(dir)?.default_branch = branch.clone();
+        Ok(branch)
+    }
+
+    /// Returns the top of the working tree that contains `dir`.
+    pub fn repository_root(&self, dir: impl AsRef<Path>) -> Result<Option<PathBuf>, PjiError> {
+        repo_root_of(&self.git, dir.as_ref())
+    }
+
+    /// Returns `path` (relative to `dir`) relative to the top of the working tree.
+    pub fn repository_relative_path(
+        &self,
+        dir: impl AsRef<Path>,
+        path: impl AsRef<Path>,
+    ) -> Result<Option<String>, PjiError> {
+        let dir = dir.as_ref();
+        let Some(root) = repo_root_of(&self.git, dir)? else {
+            return Ok(None);
+        };
+        // git reports the root with symlinks resolved, so resolve `dir` the same way.
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let root = root.canonicalize().unwrap_or(root);
+        Ok(relative_to_root(&root, &dir, path.as_ref()))
+    }
+
+    /// Returns whether `dir` has uncommitted changes, or `None` if it isn't a repository.
+    pub fn has_uncommitted_changes(&self, dir: impl AsRef<Path>) -> Result<Option<bool>, PjiError> {
+        has_uncommitted_changes(&self.git, dir.as_ref())
+    }
+
+    pub fn latest_tag(&self, dir: impl AsRef<Path>) -> Result<Option<String>, PjiError> {
+        latest_tag(&self.git, dir.as_ref())
+    }
+
+    /// Returns whether HEAD was committed at or after `cutoff`; `false` for an
+    /// empty repository.
+    pub fn committed_since(
+        &self,
+        dir: impl AsRef<Path>,
+        cutoff: DateTime<Utc>,
+    ) -> Result<bool, PjiError> {
+        committed_since(&self.git, dir.as_ref(), cutoff)
+    }
+
+    /// Returns the committer date of HEAD, or `None` for an empty repository.
+    pub fn last_commit_time(
+        &self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Option<DateTime<Utc>>, PjiError> {
+        last_commit_time(&self.git, dir.as_ref())
+    }
+
+    pub fn local_branches(&self, repo_dir: impl AsRef<Path>) -> Vec<String> {
+        list_local_branches(&self.git, &repo_dir.as_ref().to_path_buf())
+    }
+
+    pub fn remote_branches(&self, repo_dir: impl AsRef<Path>) -> Vec<String> {
+        list_remote_branches(&self.git, &repo_dir.as_ref().to_path_buf())
+    }
+}
+
+fn scan_root(
+    git: &GitRunner,
+    root: &Path,
+    layout: Layout,
+    deep: bool,
+) -> Result<RootScan, PjiError> {
+    if !root.is_dir() {
+        return Ok(RootScan {
+            discovered: Vec::new(),
+            invalid_paths: Vec::new(),
+            issues: Vec::new(),
+        });
+    }
+
+    let mut discovered = Vec::new();
+    let mut invalid_paths = Vec::new();
+    let mut issues = Vec::new();
+
+    let mut repo_dirs = Vec::new();
+    if deep {
+        find_git_dirs(root, 0, &mut repo_dirs)?;
+    } else {
+        let mut dirs = vec![root.to_path_buf()];
+        for _ in 0..layout.depth() {
+            let mut children = Vec::new();
+            for dir in &dirs {
+                children.extend(list_dir(dir)?);
+            }
+            dirs = children;
+        }
+        repo_dirs.extend(
+            dirs.into_iter()
+                .filter(|dir| !is_linked_worktree(dir) && !is_worktree_dir(dir)),
+        );
+    }
+    // read_dir order is unspecified; sort so metadata is saved in a stable order.
+    repo_dirs.sort();
+
+    let remote_urls = remote_urls(git, &repo_dirs);
+    for (repo_dir, remote_url) in repo_dirs.into_iter().zip(remote_urls) {
+        let repo_url = match remote_url {
+            Ok(Some(repo_url)) => repo_url,
+            Ok(None) => {
+                invalid_paths.push(repo_dir.clone());
+                issues.push(ScanIssue {
+                    path: repo_dir,
+                    message: "remote.origin.url not found".to_string(),
+                });
+                continue;
+            }
+            Err(PjiError::Io(err)) => return Err(PjiError::Io(err)),
+            Err(err) => {
+                invalid_paths.push(repo_dir.clone());
+                issues.push(ScanIssue {
+                    path: repo_dir,
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let mut repo = match PjiRepo::try_new(&repo_url, root, layout) {
+            Ok(repo) => repo,
+            Err(err) => {
+                invalid_paths.push(repo_dir.clone());
+                issues.push(ScanIssue {
+                    path: repo_dir,
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+        if deep {
+            repo.dir = repo_dir;
+            discovered.push(repo.into());
+        } else if repo.dir == repo_dir {
+            discovered.push(repo.into());
+        } else {
+            invalid_paths.push(repo_dir.clone());
+            issues.push(ScanIssue {
+                path: repo_dir,
+                message: "repository remote does not match pji directory layout".to_string(),
+            });
+        }
+    }
+
+    Ok(RootScan {
+        discovered,
+        invalid_paths,
+        issues,
+    })
+}
+
+/// Reads `remote.origin.url` of every directory, in the order given.
+fn remote_urls(git: &GitRunner, dirs: &[PathBuf]) -> Vec<Result<Option<String>, PjiError>> {
+    in_parallel(dirs, |dir| try_get_repo_from_dir(git, dir))
+}
+
+/// Maps `f` over `items` on up to `GIT_WORKERS` threads, keeping the order.
+/// Meant for work that mostly waits on git subprocesses.
+fn in_parallel<T, R>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let chunk_size = items.len().div_ceil(GIT_WORKERS);
+    let f = &f;
+    thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("git worker panicked"))
+            .collect()
+    })
+}
+
+fn clone_args(url: &str, dir: &Path, options: &CloneOptions) -> Vec<OsString> {
+    let mut args = vec![OsString::from("clone")];
+    if let Some(depth) = options.depth {
+        args.push("--depth".into());
+        args.push(depth.to_string().into());
+    }
+    if let Some(branch) = &options.branch {
+        args.push("--branch".into());
+        args.push(branch.into());
+    }
+    if options.recurse_submodules {
+        args.push("--recurse-submodules".into());
     }
+    args.push(url.into());
+    args.push(dir.into());
+    args
+}
 
-    pub fn remote_branches(&self, repo_dir: impl AsRef<Path>) -> Vec<String> {
-        list_remote_branches(&repo_dir.as_ref().to_path_buf())
+/// Clones `url` into `dir`. A failed clone leaves no `dir` behind. Without
+/// `prompts`, git and ssh fail instead of asking for anything.
+fn clone_repo(
+    git: &GitRunner,
+    url: &str,
+    dir: &Path,
+    options: &CloneOptions,
+    prompts: bool,
+) -> Result<(), PjiError> {
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    let args = clone_args(url, dir, options);
+    let mut command = Command::new("git");
+    command.args(&args);
+    if !prompts {
+        without_prompts(&mut command);
+    }
+    removing_failed_clone(dir, || {
+        let output = git.clone_output(&mut command)?;
+        if !output.status.success() {
+            return Err(PjiError::GitCommand {
+                command: format!(
+                    "git {}",
+                    args.iter()
+                        .map(|arg| redact_url_credentials(&arg.to_string_lossy()))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ),
+                stderr: command_error_output(&output),
+            });
+        }
+        Ok(())
+    })
 }
 
-fn scan_root(root: &Path) -> Result<ScanReport, PjiError> {
-    if !root.is_dir() {
-        return Ok(ScanReport {
-            added: Vec::new(),
-            invalid_paths: Vec::new(),
-            issues: Vec::new(),
-        });
+/// Runs `clone` and removes `dir` again if it fails and `dir` didn't exist
+/// before. git cleans up a failed clone itself, but not when it is killed at
+/// the clone timeout, and the half-written checkout it leaves would later be
+/// tracked as a complete clone.
+fn removing_failed_clone(
+    dir: &Path,
+    clone: impl FnOnce() -> Result<(), PjiError>,
+) -> Result<(), PjiError> {
+    let existed = dir.exists();
+    let result = clone();
+    if result.is_err() && !existed {
+        let _ = std::fs::remove_dir_all(dir);
     }
+    result
+}
 
-    let mut added = Vec::new();
-    let mut invalid_paths = Vec::new();
-    let mut issues = Vec::new();
+/// Keeps `command` from prompting: git won't ask for credentials, and ssh
+/// fails instead of asking for a passphrase or to confirm a host key. A
+/// `GIT_SSH_COMMAND` that is already set is kept.
+fn without_prompts(command: &mut Command) -> &mut Command {
+    let ssh = std::env::var("GIT_SSH_COMMAND").unwrap_or_else(|_| "ssh".to_string());
+    command
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_SSH_COMMAND", format!("{ssh} -o BatchMode=yes"))
+}
 
-    for hostname_dir in list_dir(&root.to_path_buf())? {
-        for user_dir in list_dir(&hostname_dir)? {
-            for repo_dir in list_dir(&user_dir)? {
-                if is_linked_worktree(&repo_dir) || is_worktree_dir(&repo_dir) {
-                    continue;
-                }
+fn sync_clone(git: &GitRunner, repo: &Repository, options: &CloneOptions) -> SyncOutcome {
+    match clone_repo(git, &repo.git.original, &repo.dir, options, false) {
+        Ok(()) => SyncOutcome::Cloned,
+        Err(PjiError::GitCommand { stderr, .. }) if is_auth_error(&stderr) => {
+            SyncOutcome::AuthRequired(stderr)
+        }
+        Err(PjiError::GitCommand { stderr, .. }) => SyncOutcome::Failed(stderr),
+        Err(err) => SyncOutcome::Failed(err.to_string()),
+    }
+}
 
-                let repo_url = match try_get_repo_from_dir(&repo_dir) {
-                    Ok(Some(repo_url)) => repo_url,
-                    Ok(None) => {
-                        invalid_paths.push(repo_dir.clone());
-                        issues.push(ScanIssue {
-                            path: repo_dir,
-                            message: "remote.origin.url not found".to_string(),
-                        });
-                        continue;
-                    }
-                    Err(PjiError::Io(err)) => return Err(PjiError::Io(err)),
-                    Err(err) => {
-                        invalid_paths.push(repo_dir.clone());
-                        issues.push(ScanIssue {
-                            path: repo_dir,
-                            message: err.to_string(),
-                        });
-                        continue;
-                    }
-                };
+fn sync_pull(git: &GitRunner, dir: &Path) -> SyncOutcome {
+    sync_outcome(
+        git.clone_output(
+            without_prompts(Command::new("git").args(["pull", "--ff-only"])).current_dir(dir),
+        ),
+        SyncOutcome::Pulled,
+    )
+}
 
-                let repo = match PjiRepo::try_new(&repo_url, root) {
-                    Ok(repo) => repo,
-                    Err(err) => {
-                        invalid_paths.push(repo_dir.clone());
-                        issues.push(ScanIssue {
-                            path: repo_dir,
-                            message: err.to_string(),
-                        });
-                        continue;
-                    }
-                };
-                if repo.dir == repo_dir {
-                    added.push(repo.into());
-                } else {
-                    invalid_paths.push(repo_dir.clone());
-                    issues.push(ScanIssue {
-                        path: repo_dir,
-                        message: "repository remote does not match pji directory layout"
-                            .to_string(),
-                    });
-                }
+fn sync_outcome(
+    output: std::io::Result<std::process::Output>,
+    success: SyncOutcome,
+) -> SyncOutcome {
+    match output {
+        Ok(output) if output.status.success() => success,
+        Ok(output) => {
+            let stderr = command_error_output(&output);
+            if is_auth_error(&stderr) {
+                SyncOutcome::AuthRequired(stderr)
+            } else {
+                SyncOutcome::Failed(stderr)
             }
         }
+        Err(err) => SyncOutcome::Failed(err.to_string()),
     }
+}
 
-    Ok(ScanReport {
-        added,
-        invalid_paths,
-        issues,
-    })
+/// Whether git's error output says the remote wanted credentials.
+fn is_auth_error(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    [
+        "terminal prompts disabled",
+        "could not read username",
+        "authentication failed",
+        "permission denied (publickey",
+        "host key verification failed",
+    ]
+    .iter()
+    .any(|pattern| stderr.contains(pattern))
 }
 
 fn command_error_output(output: &std::process::Output) -> String {
@@ -454,6 +1977,30 @@ fn command_error_output(output: &std::process::Output) -> String {
     }
 }
 
+/// Collects every directory below `dir` that contains `.git`, without
+/// descending into repositories, worktree directories, or hidden directories.
+fn find_git_dirs(dir: &Path, depth: usize, repo_dirs: &mut Vec<PathBuf>) -> Result<(), PjiError> {
+    if depth >= DEEP_SCAN_MAX_DEPTH {
+        return Ok(());
+    }
+
+    for child in list_dir(&dir.to_path_buf())? {
+        let hidden = child
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+        if hidden || is_worktree_dir(&child) || is_linked_worktree(&child) {
+            continue;
+        }
+        if child.join(".git").exists() {
+            repo_dirs.push(child);
+        } else {
+            find_git_dirs(&child, depth + 1, repo_dirs)?;
+        }
+    }
+    Ok(())
+}
+
 fn is_worktree_dir(path: &Path) -> bool {
     path.file_name()
         .and_then(|name| name.to_str())
@@ -507,6 +2054,8 @@ impl From<GitProtocol> for Protocol {
         match protocol {
             GitProtocol::Ssh => Self::Ssh,
             GitProtocol::Https => Self::Https,
+            GitProtocol::Git => Self::Git,
+            GitProtocol::Unknown(value) => Self::Unknown(value),
         }
     }
 }
@@ -516,6 +2065,8 @@ impl From<Protocol> for GitProtocol {
         match protocol {
             Protocol::Ssh => Self::Ssh,
             Protocol::Https => Self::Https,
+            Protocol::Git => Self::Git,
+            Protocol::Unknown(value) => Self::Unknown(value),
         }
     }
 }
@@ -523,11 +2074,19 @@ impl From<Protocol> for GitProtocol {
 impl From<PjiRepo> for Repository {
     fn from(repo: PjiRepo) -> Self {
         Self {
+            provider: repo
+                .provider
+                .or_else(|| Provider::from_hostname(&repo.git_uri.hostname)),
+            provider_override: repo.provider,
+            web_host: None,
+            default_branch: repo.default_branch,
             git: repo.git_uri.into(),
             dir: repo.dir,
             root: repo.root,
             created_at: repo.create_time,
             last_opened_at: repo.last_open_time,
+            open_count: repo.open_count,
+            tags: repo.tags,
         }
     }
 }
@@ -540,6 +2099,10 @@ impl From<Repository> for PjiRepo {
             root: repo.root,
             create_time: repo.created_at,
             last_open_time: repo.last_opened_at,
+            open_count: repo.open_count,
+            tags: repo.tags,
+            provider: repo.provider_override,
+            default_branch: repo.default_branch,
         }
     }
 }
@@ -561,11 +2124,1224 @@ mod tests {
     #[test]
     fn computes_repository_path() {
         let git = GitUrl::parse("https://github.com/zhanba/pji.git").unwrap();
-        let path = Pji::repository_path("/tmp/pji", &git);
+        let path = Pji::new(PjiConfig::default(), PjiMetadata::default())
+            .repository_path("/tmp/pji", &git);
 
         assert_eq!(path, PathBuf::from("/tmp/pji/github.com/zhanba/pji"));
     }
 
+    fn repository(url: &str) -> Repository {
+        PjiRepo::try_new(url, Path::new("/tmp/pji"), Layout::default())
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn builds_https_urls_for_git_protocol_repositories() {
+        let repo = repository("git://github.com/zhanba/pji.git");
+
+        assert_eq!(repo.git.protocol, Protocol::Git);
+        assert_eq!(
+            repo.home_url(),
+            Some("https://github.com/zhanba/pji".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_gitlab_urls() {
+        let repo = repository("git@gitlab.com:gitlab-org/gitlab.git");
+
+        assert_eq!(
+            repo.home_url(),
+            Some("https://gitlab.com/gitlab-org/gitlab".to_string())
+        );
+        assert_eq!(
+            repo.pull_request_url(Some(42)),
+            Some("https://gitlab.com/gitlab-org/gitlab/-/merge_requests/42".to_string())
+        );
+        assert_eq!(
+            repo.pull_request_url(None),
+            Some("https://gitlab.com/gitlab-org/gitlab/-/merge_requests".to_string())
+        );
+        assert_eq!(
+            repo.issue_url(Some(7)),
+            Some("https://gitlab.com/gitlab-org/gitlab/-/issues/7".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_bitbucket_urls() {
+        let repo = repository("git@bitbucket.org:atlassian/python-bitbucket.git");
+
+        assert_eq!(
+            repo.pull_request_url(Some(42)),
+            Some("https://bitbucket.org/atlassian/python-bitbucket/pull-requests/42".to_string())
+        );
+        assert_eq!(
+            repo.pull_request_url(None),
+            Some("https://bitbucket.org/atlassian/python-bitbucket/pull-requests".to_string())
+        );
+        assert_eq!(
+            repo.issue_url(Some(7)),
+            Some("https://bitbucket.org/atlassian/python-bitbucket/issues/7".to_string())
+        );
+        assert_eq!(
+            repo.issue_url(None),
+            Some("https://bitbucket.org/atlassian/python-bitbucket/issues".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_bitbucket_server_urls() {
+        let mut repo = repository("git@stash.example.com:plat/billing.git");
+        repo.provider = Some(Provider::BitbucketServer);
+
+        assert_eq!(
+            repo.home_url(),
+            Some("https://stash.example.com/projects/PLAT/repos/billing/browse".to_string())
+        );
+        assert_eq!(
+            repo.pull_request_url(Some(12)),
+            Some(
+                "https://stash.example.com/projects/PLAT/repos/billing/pull-requests/12/overview"
+                    .to_string()
+            )
+        );
+        assert_eq!(repo.issue_url(Some(1)), None);
+    }
+
+    #[test]
+    fn builds_gitlab_subgroup_urls() {
+        let repo = repository("git@gitlab.com:group/subgroup/project.git");
+        assert_eq!(
+            repo.home_url(),
+            Some("https://gitlab.com/group/subgroup/project".to_string())
+        );
+        assert_eq!(
+            repo.issue_url(Some(5)),
+            Some("https://gitlab.com/group/subgroup/project/-/issues/5".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_gitea_urls() {
+        let mut repo = repository("git@git.example.com:infra/deploy.git");
+        repo.provider = Some(Provider::Gitea);
+
+        assert_eq!(
+            repo.pull_request_url(Some(3)),
+            Some("https://git.example.com/infra/deploy/pulls/3".to_string())
+        );
+        assert_eq!(
+            repo.file_commits_url("main", "src/lib.rs"),
+            Some("https://git.example.com/infra/deploy/commits/branch/main/src/lib.rs".to_string())
+        );
+    }
+
+    #[test]
+    fn configured_gitlab_host_builds_issue_urls() {
+        let mut pji = pji_with_repos(&["git@git.mycompany.com:team/app.git"]);
+        pji.config
+            .providers
+            .insert("git.mycompany.com".to_string(), Provider::GitLab);
+
+        assert_eq!(
+            pji.repositories()[0].issue_url(Some(5)),
+            Some("https://git.mycompany.com/team/app/-/issues/5".to_string())
+        );
+    }
+
+    #[test]
+    fn configured_providers_apply_to_self_hosted_hosts() {
+        let mut pji = pji_with_repos(&["git@stash.example.com:plat/billing.git"]);
+        assert_eq!(pji.repositories()[0].provider, None);
+
+        pji.config
+            .providers
+            .insert("stash.example.com".to_string(), Provider::BitbucketServer);
+        assert_eq!(
+            pji.repositories()[0].provider,
+            Some(Provider::BitbucketServer)
+        );
+    }
+
+    #[test]
+    fn repository_provider_override_wins_over_config() {
+        let mut pji = pji_with_repos(&["git@code.example.com:team/app.git"]);
+        pji.config
+            .providers
+            .insert("code.example.com".to_string(), Provider::GitLab);
+        let dir = pji.repositories()[0].dir.clone();
+
+        pji.set_repository_provider(&dir, Some(Provider::GitHub))
+            .unwrap();
+        let overridden = &pji.repositories()[0];
+        assert_eq!(overridden.provider, Some(Provider::GitHub));
+        assert_eq!(
+            overridden.issue_url(Some(3)),
+            Some("https://code.example.com/team/app/issues/3".to_string())
+        );
+
+        pji.set_repository_provider(&dir, None).unwrap();
+        assert_eq!(pji.repositories()[0].provider, Some(Provider::GitLab));
+    }
+
+    #[test]
+    fn host_aliases_resolve_web_urls_of_ssh_aliases() {
+        let mut pji = pji_with_repos(&["git@github-work:zhanba/pji.git"]);
+        assert_eq!(pji.repositories()[0].home_url(), None);
+
+        pji.config
+            .host_aliases
+            .insert("github-work".to_string(), "github.com".to_string());
+        let repo = &pji.repositories()[0];
+        assert_eq!(repo.provider, Some(Provider::GitHub));
+        assert_eq!(
+            repo.home_url(),
+            Some("https://github.com/zhanba/pji".to_string())
+        );
+        assert_eq!(repo.git.original, "git@github-work:zhanba/pji.git");
+        assert_eq!(repo.dir, PathBuf::from("/tmp/pji/github-work/zhanba/pji"));
+    }
+
+    #[test]
+    fn builds_org_wide_pull_request_search_urls() {
+        let repo = repository("git@github.com:zhanba/pji.git");
+        assert_eq!(
+            repo.my_pull_requests_url(true),
+            Some("https://github.com/pulls?q=is:pr+org:zhanba+author:@me".to_string())
+        );
+        assert_eq!(
+            repo.my_pull_requests_url(false),
+            Some("https://github.com/zhanba/pji/pulls?q=is:pr+author:@me".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").my_pull_requests_url(true),
+            Some("https://gitlab.com/dashboard/merge_requests".to_string())
+        );
+    }
+
+    #[test]
+    fn upstream_remote_urls_point_at_the_fork() {
+        let pji = pji_with_repos(&["git@github.com:zhanba/pji.git"]);
+        let repo = pji.repositories().remove(0);
+
+        let fork = pji.with_remote_url(repo.clone(), "git@github.com:contributor/pji.git");
+        assert_eq!(fork.dir, repo.dir);
+        assert_eq!(
+            fork.pull_request_url(None),
+            Some("https://github.com/contributor/pji/pull".to_string())
+        );
+        assert_eq!(
+            fork.compare_url("v1.0.0", "feature"),
+            Some("https://github.com/contributor/pji/compare/v1.0.0...feature".to_string())
+        );
+    }
+
+    /// Creates `dir` as a git repository whose `origin` remote is `url`.
+    fn init_repo_with_origin(dir: &Path, url: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        for args in [&["init", "-q"][..], &["remote", "add", "origin", url]] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[test]
+    fn scan_root_walks_host_owner_repo_dirs() {
+        let root = std::env::temp_dir().join(format!("pji-scan-root-{}", std::process::id()));
+        let repo_dir = root.join("github.com/zhanba/pji");
+        std::fs::create_dir_all(root.join("github.com/zhanba/pji.worktrees/feature")).unwrap();
+        std::fs::create_dir_all(root.join("github.com/zhanba/notes")).unwrap();
+        init_repo_with_origin(&repo_dir, "git@github.com:zhanba/pji.git");
+
+        let pji = Pji::new(PjiConfig::default(), PjiMetadata::default());
+        let report = pji.scan_root(&root).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(report.discovered.len(), 1);
+        assert_eq!(report.discovered[0].dir, repo_dir);
+        assert_eq!(report.discovered[0].provider, Some(Provider::GitHub));
+        assert!(pji.repositories().is_empty());
+        assert_eq!(
+            report.invalid_paths,
+            vec![root.join("github.com/zhanba/notes")]
+        );
+    }
+
+    #[test]
+    fn scan_root_follows_a_flat_layout() {
+        let root = std::env::temp_dir().join(format!("pji-scan-flat-{}", std::process::id()));
+        let repo_dir = root.join("pji");
+        std::fs::create_dir_all(root.join("pji.worktrees/feature")).unwrap();
+        init_repo_with_origin(&repo_dir, "git@github.com:zhanba/pji.git");
+
+        let flat = scan_root(&GitRunner::default(), &root, Layout::Flat, false).unwrap();
+        let nested = scan_root(&GitRunner::default(), &root, Layout::default(), false).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(flat.discovered.len(), 1);
+        assert_eq!(flat.discovered[0].dir, repo_dir);
+        assert!(flat.issues.is_empty());
+        assert!(nested.discovered.is_empty());
+    }
+
+    #[test]
+    fn deep_scan_finds_repositories_outside_the_layout() {
+        let root = std::env::temp_dir().join(format!("pji-deep-scan-{}", std::process::id()));
+        let layout_dir = root.join("github.com/zhanba/pji");
+        let loose_dir = root.join("archive/2024/dotfiles");
+        for (dir, url) in [
+            (&layout_dir, "git@github.com:zhanba/pji.git"),
+            (&loose_dir, "https://github.com/zhanba/dotfiles.git"),
+        ] {
+            init_repo_with_origin(dir, url);
+        }
+        std::fs::create_dir_all(root.join(".cache/nested")).unwrap();
+
+        let standard = scan_root(&GitRunner::default(), &root, Layout::default(), false).unwrap();
+        let deep = scan_root(&GitRunner::default(), &root, Layout::default(), true).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(standard.discovered.len(), 1);
+        let dirs = deep
+            .discovered
+            .iter()
+            .map(|repo| repo.dir.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(dirs, vec![loose_dir, layout_dir]);
+        assert_eq!(deep.discovered[0].git.name, "dotfiles");
+        assert!(deep.issues.is_empty());
+    }
+
+    #[test]
+    fn export_round_trips_repositories_and_tags() {
+        let mut repo = PjiRepo::try_new(
+            "git@github.com:zhanba/pji.git",
+            Path::new("/tmp/pji"),
+            Layout::default(),
+        )
+        .unwrap();
+        repo.add_tags(["oss"]);
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&repo);
+
+        let json = Pji::new(PjiConfig::default(), metadata)
+            .export_json()
+            .unwrap();
+        let repos = Pji::parse_export(&json).unwrap();
+
+        assert_eq!(repos.len(), 1);
+        assert_eq!(repos[0].git.original, "git@github.com:zhanba/pji.git");
+        assert_eq!(repos[0].tags, ["oss"]);
+        assert!(matches!(
+            Pji::parse_export("[]"),
+            Err(PjiError::InvalidExport(_))
+        ));
+    }
+
+    #[test]
+    fn resolve_repository_from_a_linked_worktree() {
+        let base = std::env::temp_dir().join(format!("pji-resolve-wt-{}", std::process::id()));
+        let real_root = base.join("real");
+        let repo_dir = real_root.join("github.com/zhanba/pji");
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        for args in [
+            &["init", "-q"][..],
+            &[
+                "-c",
+                "user.name=pji",
+                "-c",
+                "user.email=pji@example.com",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "init",
+            ],
+            &[
+                "worktree",
+                "add",
+                "-q",
+                "-b",
+                "feature",
+                "../pji.worktrees/feature",
+            ],
+        ] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&repo_dir)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        let cwd = real_root.join("github.com/zhanba/pji.worktrees/feature/src");
+        std::fs::create_dir_all(&cwd).unwrap();
+
+        let repo = PjiRepo::try_new(
+            "git@github.com:zhanba/pji.git",
+            &real_root,
+            Layout::default(),
+        )
+        .unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&repo);
+        let resolved = Pji::new(PjiConfig::default(), metadata).resolve_repository(&cwd);
+
+        // The same repository tracked under a symlink to the root.
+        #[cfg(unix)]
+        let resolved_through_link = {
+            let link_root = base.join("link");
+            std::os::unix::fs::symlink(&real_root, &link_root).unwrap();
+            let repo = PjiRepo::try_new(
+                "git@github.com:zhanba/pji.git",
+                &link_root,
+                Layout::default(),
+            )
+            .unwrap();
+            let mut metadata = PjiMetadata::default();
+            metadata.add_repo(&repo);
+            Pji::new(PjiConfig::default(), metadata)
+                .resolve_repository(link_root.join("github.com/zhanba/pji.worktrees/feature"))
+        };
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(resolved.unwrap().dir, repo_dir);
+        #[cfg(unix)]
+        assert_eq!(
+            resolved_through_link.unwrap().dir,
+            base.join("link/github.com/zhanba/pji")
+        );
+    }
+
+    #[test]
+    fn resolve_repository_picks_the_innermost_of_nested_repositories() {
+        let root = std::env::temp_dir().join(format!("pji-resolve-nested-{}", std::process::id()));
+        let outer =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", &root, Layout::default()).unwrap();
+        let mut inner =
+            PjiRepo::try_new("git@github.com:zhanba/vendor.git", &root, Layout::default()).unwrap();
+        inner.dir = outer.dir.join("vendor/lib");
+        let cwd = inner.dir.join("src");
+        std::fs::create_dir_all(outer.dir.join(".git")).unwrap();
+        std::fs::create_dir_all(inner.dir.join(".git")).unwrap();
+        std::fs::create_dir_all(&cwd).unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&outer).add_repo(&inner);
+        let pji = Pji::new(PjiConfig::default(), metadata);
+
+        let from_inner = pji.resolve_repository(&cwd);
+        let from_outer = pji.resolve_repository(outer.dir.join("vendor"));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(from_inner.unwrap().dir, inner.dir);
+        assert_eq!(from_outer.unwrap().dir, outer.dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_repository_through_a_symlinked_workspace_root() {
+        let base = std::env::temp_dir().join(format!("pji-resolve-link-{}", std::process::id()));
+        let real_root = base.join("real");
+        let link_root = base.join("link");
+        let repo = PjiRepo::try_new(
+            "git@github.com:zhanba/pji.git",
+            &real_root,
+            Layout::default(),
+        )
+        .unwrap();
+        std::fs::create_dir_all(repo.dir.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.dir.join("src")).unwrap();
+        std::os::unix::fs::symlink(&real_root, &link_root).unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&repo);
+        let pji = Pji::new(PjiConfig::default(), metadata);
+
+        let resolved = pji.resolve_repository(link_root.join("github.com/zhanba/pji/src/"));
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(resolved.unwrap().dir, repo.dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_repository_prefers_the_live_origin_remote() {
+        let base = std::env::temp_dir().join(format!("pji-resolve-{}", std::process::id()));
+        let root = base.join("root");
+        let linked_root = base.join("linked");
+        let repo_dir = root.join("github.com/zhanba/pji");
+        init_repo_with_origin(&repo_dir, "https://github.com/zhanba/pji.git");
+        std::os::unix::fs::symlink(&root, &linked_root).unwrap();
+
+        // The same checkout, tracked once through the symlinked root under a
+        // stale url, so both entries match at the same depth.
+        let mut stale =
+            PjiRepo::try_new("git@github.com:zhanba/old.git", &root, Layout::default()).unwrap();
+        stale.dir = linked_root.join("github.com/zhanba/pji");
+        let current =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", &root, Layout::default()).unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&stale).add_repo(&current);
+        let pji = Pji::new(PjiConfig::default(), metadata);
+
+        let resolved = pji.resolve_repository(&repo_dir);
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&repo_dir)
+            .args([
+                "remote",
+                "set-url",
+                "origin",
+                "git@github.com:zhanba/old.git",
+            ])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let resolved_after_rename = pji.resolve_repository(&repo_dir);
+        std::fs::remove_dir_all(&base).unwrap();
+
+        let resolved = resolved.unwrap();
+        assert_eq!(resolved.git.name, "pji");
+        assert_eq!(resolved.dir, repo_dir);
+        let resolved_after_rename = resolved_after_rename.unwrap();
+        assert_eq!(resolved_after_rename.git.name, "old");
+        assert_eq!(resolved_after_rename.dir, stale.dir);
+    }
+
+    #[test]
+    fn move_worktree_refuses_an_existing_destination() {
+        let pji = Pji::new(PjiConfig::default(), PjiMetadata::default());
+        let existing = std::env::temp_dir();
+
+        let result = pji.move_worktree("/work/pji", "/work/pji.worktrees/feature", &existing);
+
+        assert!(matches!(result, Err(PjiError::DestinationExists(path)) if path == existing));
+    }
+
+    #[test]
+    fn remove_root_refuses_roots_with_repositories() {
+        let mut pji = pji_with_repos(&["git@github.com:zhanba/pji.git"]);
+        pji.add_root("/tmp/pji");
+        pji.add_root("/tmp/empty");
+
+        assert_eq!(pji.repository_count_in_root("/tmp/pji"), 1);
+        assert!(matches!(
+            pji.remove_root("/tmp/pji", false),
+            Err(PjiError::RootHasRepositories { count: 1, .. })
+        ));
+        assert!(matches!(
+            pji.remove_root("/tmp/missing", false),
+            Err(PjiError::RootNotConfigured(_))
+        ));
+
+        pji.remove_root("/tmp/empty", false).unwrap();
+        pji.remove_root("/tmp/pji", true).unwrap();
+        assert!(!pji.roots().iter().any(|root| root.starts_with("/tmp")));
+        assert_eq!(pji.repositories().len(), 1);
+    }
+
+    #[test]
+    fn prune_missing_drops_only_vanished_directories() {
+        let root = std::env::temp_dir().join(format!("pji-prune-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("github.com/zhanba/pji")).unwrap();
+        let mut metadata = PjiMetadata::default();
+        for url in [
+            "git@github.com:zhanba/pji.git",
+            "git@github.com:zhanba/gone.git",
+        ] {
+            metadata.add_repo(&PjiRepo::try_new(url, &root, Layout::default()).unwrap());
+        }
+        let mut pji = Pji::new(PjiConfig::default(), metadata);
+
+        let missing = pji.missing_repositories();
+        let pruned = pji.prune_missing();
+        let remaining = pji.repositories();
+        let still_missing = pji.missing_repositories();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(missing.len(), 1);
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].dir, root.join("github.com/zhanba/gone"));
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].dir, root.join("github.com/zhanba/pji"));
+        assert!(still_missing.is_empty());
+    }
+
+    #[test]
+    fn sync_leaves_existing_repositories_alone_without_pull() {
+        let root = std::env::temp_dir().join(format!("pji-sync-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("github.com/zhanba/pji")).unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(
+            &PjiRepo::try_new("git@github.com:zhanba/pji.git", &root, Layout::default()).unwrap(),
+        );
+
+        let synced = Pji::new(PjiConfig::default(), metadata).sync(false);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(synced.len(), 1);
+        assert_eq!(synced[0].outcome, SyncOutcome::Present);
+    }
+
+    #[test]
+    fn recognizes_git_authentication_errors() {
+        assert!(is_auth_error(
+            "fatal: could not read Username for 'https://github.com': terminal prompts disabled"
+        ));
+        assert!(is_auth_error(
+            "git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository."
+        ));
+        assert!(!is_auth_error(
+            "fatal: repository 'https://github.com/x/y/' not found"
+        ));
+    }
+
+    #[test]
+    fn sync_commands_never_prompt() {
+        let mut command = Command::new("git");
+        without_prompts(&mut command);
+        let env = |name: &str| {
+            command
+                .get_envs()
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, value)| value)
+                .map(|value| value.to_string_lossy().into_owned())
+        };
+
+        assert_eq!(env("GIT_TERMINAL_PROMPT").as_deref(), Some("0"));
+        assert!(env("GIT_SSH_COMMAND")
+            .unwrap()
+            .ends_with(" -o BatchMode=yes"));
+    }
+
+    #[test]
+    fn track_existing_clone_only_accepts_the_same_origin() {
+        let root = std::env::temp_dir().join(format!("pji-existing-{}", std::process::id()));
+        for (name, remote) in [
+            ("pji", "https://github.com/zhanba/pji.git"),
+            ("other", "git@github.com:someone/else.git"),
+        ] {
+            let dir = root.join("github.com/zhanba").join(name);
+            init_repo_with_origin(&dir, remote);
+        }
+
+        let mut pji = Pji::new(PjiConfig::default(), PjiMetadata::default());
+        let tracked = pji.track_existing_clone("git@github.com:zhanba/pji.git", &root);
+        let other = pji.track_existing_clone("git@github.com:zhanba/other.git", &root);
+        let absent = pji.track_existing_clone("git@github.com:zhanba/absent.git", &root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            tracked.unwrap().unwrap().dir,
+            root.join("github.com/zhanba/pji")
+        );
+        assert_eq!(pji.repositories().len(), 1);
+        assert!(matches!(
+            other,
+            Err(PjiError::DirectoryHasOtherOrigin { origin, .. })
+                if origin == "git@github.com:someone/else.git"
+        ));
+        assert!(absent.unwrap().is_none());
+    }
+
+    #[test]
+    fn importing_twice_tracks_existing_clones_once() {
+        let root = std::env::temp_dir().join(format!("pji-import-{}", std::process::id()));
+        let mut source = pji_with_repos(&[
+            "git@github.com:zhanba/pji.git",
+            "https://github.com/zhanba/dotfiles.git",
+        ]);
+        source
+            .add_repository_tags("/tmp/pji/github.com/zhanba/pji", &["work".to_string()])
+            .unwrap();
+        let exported = Pji::parse_export(&source.export_json().unwrap()).unwrap();
+        init_repo_with_origin(
+            &root.join("github.com/zhanba/pji"),
+            "git@github.com:zhanba/pji.git",
+        );
+        init_repo_with_origin(
+            &root.join("github.com/zhanba/dotfiles"),
+            "https://github.com/zhanba/dotfiles.git",
+        );
+
+        let mut pji = Pji::new(PjiConfig::default(), PjiMetadata::default());
+        let import = |pji: &mut Pji| {
+            exported
+                .iter()
+                .map(|repo| pji.import_repository(repo, &root, &CloneOptions::default()))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let first = import(&mut pji);
+        let second = import(&mut pji);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let first = first.unwrap();
+        assert!(first
+            .iter()
+            .all(|outcome| matches!(outcome, ImportOutcome::TrackedExisting(_))));
+        assert!(second
+            .unwrap()
+            .iter()
+            .all(|outcome| matches!(outcome, ImportOutcome::AlreadyTracked)));
+        let repos = pji.repositories();
+        assert_eq!(repos.len(), 2);
+        let imported_pji = repos.iter().find(|repo| repo.git.name == "pji").unwrap();
+        assert_eq!(imported_pji.dir, root.join("github.com/zhanba/pji"));
+        assert_eq!(imported_pji.tags, ["work"]);
+    }
+
+    #[test]
+    fn repository_default_branch_is_recorded_and_refreshed_by_scan() {
+        let root = std::env::temp_dir().join(format!("pji-default-branch-{}", std::process::id()));
+        let repo =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", &root, Layout::default()).unwrap();
+        let set_head = |branch: &str| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&repo.dir)
+                .args(["symbolic-ref", "refs/remotes/origin/HEAD"])
+                .arg(format!("refs/remotes/origin/{branch}"))
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        init_repo_with_origin(&repo.dir, "git@github.com:zhanba/pji.git");
+        set_head("trunk");
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&repo);
+        let mut pji = Pji::new(PjiConfig::default(), metadata);
+        pji.add_root(&root);
+
+        let first = pji.repository_default_branch(&repo.dir).unwrap();
+        set_head("develop");
+        let stored = pji.repository_default_branch(&repo.dir).unwrap();
+        let scan = pji.scan();
+        let refreshed = pji.repository_default_branch(&repo.dir).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(first.as_deref(), Some("trunk"));
+        assert_eq!(stored.as_deref(), Some("trunk"));
+        assert!(scan.unwrap().added.is_empty());
+        assert_eq!(refreshed.as_deref(), Some("develop"));
+    }
+
+    #[test]
+    fn move_repository_relocates_the_directory_into_another_root() {
+        let base = std::env::temp_dir().join(format!("pji-move-{}", std::process::id()));
+        let (old_root, new_root) = (base.join("old"), base.join("new"));
+        std::fs::create_dir_all(old_root.join("github.com/zhanba/pji/.git")).unwrap();
+        std::fs::create_dir_all(&new_root).unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(
+            &PjiRepo::try_new(
+                "git@github.com:zhanba/pji.git",
+                &old_root,
+                Layout::default(),
+            )
+            .unwrap(),
+        );
+        let config = PjiConfig {
+            roots: vec![old_root.clone(), new_root.clone()],
+            ..PjiConfig::default()
+        };
+        let mut pji = Pji::new(config, metadata);
+
+        let unconfigured =
+            pji.move_repository(old_root.join("github.com/zhanba/pji"), base.join("other"));
+        let moved = pji.move_repository(old_root.join("github.com/zhanba/pji"), &new_root);
+        let moved_git_dir = new_root.join("github.com/zhanba/pji/.git").is_dir();
+        let old_gone = !old_root.join("github.com/zhanba/pji").exists();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert!(matches!(unconfigured, Err(PjiError::RootNotConfigured(_))));
+        let moved = moved.unwrap();
+        assert_eq!(moved.dir, new_root.join("github.com/zhanba/pji"));
+        assert_eq!(moved.root, new_root);
+        assert_eq!(pji.repositories()[0].dir, moved.dir);
+        assert!(moved_git_dir);
+        assert!(old_gone);
+    }
+
+    #[test]
+    fn verify_reports_repositories_whose_remote_changed() {
+        let root = std::env::temp_dir().join(format!("pji-verify-{}", std::process::id()));
+        for (name, remote) in [
+            ("pji", "git@github.com:zhanba/pji.git"),
+            ("moved", "git@github.com:someone/elsewhere.git"),
+        ] {
+            let dir = root.join("github.com/zhanba").join(name);
+            init_repo_with_origin(&dir, remote);
+        }
+
+        let mut metadata = PjiMetadata::default();
+        for url in [
+            "https://github.com/zhanba/pji.git",
+            "git@github.com:zhanba/moved.git",
+            "git@github.com:zhanba/missing.git",
+        ] {
+            metadata.add_repo(&PjiRepo::try_new(url, &root, Layout::default()).unwrap());
+        }
+        let issues = Pji::new(PjiConfig::default(), metadata).verify();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let issues = issues.unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].path, root.join("github.com/zhanba/missing"));
+        assert_eq!(issues[0].message, "directory does not exist");
+        assert_eq!(issues[1].path, root.join("github.com/zhanba/moved"));
+        assert!(issues[1]
+            .message
+            .contains("git@github.com:someone/elsewhere.git"));
+    }
+
+    #[test]
+    fn builds_clone_args_for_depth_and_branch() {
+        let url = "git@github.com:zhanba/pji.git";
+        let dir = Path::new("/tmp/pji/github.com/zhanba/pji");
+        let args = |options: CloneOptions| {
+            clone_args(url, dir, &options)
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let dir = dir.display().to_string();
+
+        assert_eq!(args(CloneOptions::default()), ["clone", url, &dir]);
+        assert_eq!(
+            args(CloneOptions {
+                depth: Some(1),
+                ..CloneOptions::default()
+            }),
+            ["clone", "--depth", "1", url, &dir]
+        );
+        assert_eq!(
+            args(CloneOptions {
+                branch: Some("dev".to_string()),
+                ..CloneOptions::default()
+            }),
+            ["clone", "--branch", "dev", url, &dir]
+        );
+        assert_eq!(
+            args(CloneOptions {
+                depth: Some(5),
+                branch: Some("dev".to_string()),
+                ..CloneOptions::default()
+            }),
+            ["clone", "--depth", "5", "--branch", "dev", url, &dir]
+        );
+    }
+
+    #[test]
+    fn recurses_into_submodules_only_when_asked() {
+        let dir = Path::new("/tmp/pji/github.com/zhanba/pji");
+        let has_flag = |options: &CloneOptions| {
+            clone_args("git@github.com:zhanba/pji.git", dir, options)
+                .iter()
+                .any(|arg| arg == "--recurse-submodules")
+        };
+
+        assert!(!has_flag(&CloneOptions::default()));
+        assert!(has_flag(&CloneOptions {
+            recurse_submodules: true,
+            ..CloneOptions::default()
+        }));
+    }
+
+    #[test]
+    fn routes_bare_references_by_provider() {
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").reference_url(12),
+            Some("https://github.com/zhanba/pji/pull/12".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").reference_url(12),
+            None
+        );
+    }
+
+    #[test]
+    fn scores_frecency_by_count_and_recency() {
+        let now = Utc::now();
+        let mut repo = repository("git@github.com:zhanba/pji.git");
+        repo.last_opened_at = now;
+        repo.open_count = 0;
+        assert_eq!(repo.frecency(now), 4.0);
+
+        repo.open_count = 3;
+        assert_eq!(repo.frecency(now), 16.0);
+
+        repo.last_opened_at = now - chrono::TimeDelta::days(3);
+        assert_eq!(repo.frecency(now), 2.0);
+
+        // A repository opened often last month outranks one opened once today.
+        let mut daily = repository("git@github.com:zhanba/dotfiles.git");
+        daily.last_opened_at = now - chrono::TimeDelta::hours(3);
+        repo.last_opened_at = now - chrono::TimeDelta::days(30);
+        repo.open_count = 20;
+        assert!(repo.frecency(now) > daily.frecency(now));
+
+        repo.open_count = u32::MAX;
+        assert_eq!(repo.frecency(now), (f64::from(u32::MAX) + 1.0) * 0.25);
+    }
+
+    #[test]
+    fn orders_repositories_by_name_created_and_recent() {
+        let now = Utc::now();
+        let mut old = repository("git@github.com:zhanba/Zoo.git");
+        old.created_at = now - chrono::TimeDelta::days(10);
+        old.last_opened_at = now;
+        let mut new = repository("git@github.com:zhanba/apple.git");
+        new.created_at = now;
+        new.last_opened_at = now - chrono::TimeDelta::days(1);
+        let names = |order: RepositoryOrder| {
+            let mut repos = vec![old.clone(), new.clone()];
+            order.sort(&mut repos, now);
+            repos
+                .into_iter()
+                .map(|repo| repo.git.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(RepositoryOrder::Name), ["apple", "Zoo"]);
+        assert_eq!(names(RepositoryOrder::Created), ["apple", "Zoo"]);
+        assert_eq!(names(RepositoryOrder::Recent), ["Zoo", "apple"]);
+        assert_eq!(names(RepositoryOrder::Frecency), ["Zoo", "apple"]);
+    }
+
+    #[test]
+    fn builds_workflow_urls_from_names_and_files() {
+        let repo = repository("git@github.com:zhanba/pji.git");
+        assert_eq!(
+            repo.workflow_url("release"),
+            Some("https://github.com/zhanba/pji/actions/workflows/release.yml".to_string())
+        );
+        assert_eq!(
+            repo.workflow_url("release.yml"),
+            Some("https://github.com/zhanba/pji/actions/workflows/release.yml".to_string())
+        );
+        assert_eq!(
+            repo.workflow_url("ci.yaml"),
+            Some("https://github.com/zhanba/pji/actions/workflows/ci.yaml".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").workflow_url("ci"),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_security_advisory_urls() {
+        let repo = repository("git@github.com:zhanba/pji.git");
+        assert_eq!(
+            repo.security_advisories_url(false),
+            Some("https://github.com/zhanba/pji/security/advisories".to_string())
+        );
+        assert_eq!(
+            repo.security_advisories_url(true),
+            Some("https://github.com/zhanba/pji/security/advisories/new".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").security_advisories_url(false),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_release_and_actions_urls() {
+        let repo = repository("git@github.com:zhanba/pji.git");
+        assert_eq!(
+            repo.releases_url(None),
+            Some("https://github.com/zhanba/pji/releases".to_string())
+        );
+        assert_eq!(
+            repo.releases_url(Some("v1.2.3")),
+            Some("https://github.com/zhanba/pji/releases/tag/v1.2.3".to_string())
+        );
+        assert_eq!(
+            repo.actions_url(),
+            Some("https://github.com/zhanba/pji/actions".to_string())
+        );
+
+        let repo = repository("git@gitlab.com:group/project.git");
+        assert_eq!(
+            repo.releases_url(Some("v1.2.3")),
+            Some("https://gitlab.com/group/project/-/releases/v1.2.3".to_string())
+        );
+        assert_eq!(
+            repo.actions_url(),
+            Some("https://gitlab.com/group/project/-/pipelines".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_branch_urls() {
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").branch_url("feature/login"),
+            Some("https://github.com/zhanba/pji/tree/feature/login".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").branch_url("main"),
+            Some("https://gitlab.com/group/project/-/tree/main".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_blob_urls() {
+        let repo = repository("git@github.com:zhanba/pji.git");
+        assert_eq!(
+            repo.blob_url("main", "src/main.rs", Some(42)),
+            Some("https://github.com/zhanba/pji/blob/main/src/main.rs#L42".to_string())
+        );
+        assert_eq!(
+            repo.blob_url("main", "docs/my notes.md", None),
+            Some("https://github.com/zhanba/pji/blob/main/docs/my%20notes.md".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").blob_url("dev", "README.md", Some(3)),
+            Some("https://gitlab.com/group/project/-/blob/dev/README.md#L3".to_string())
+        );
+        assert_eq!(
+            repository("git@bitbucket.org:team/project.git").blob_url("main", "a.rs", Some(7)),
+            Some("https://bitbucket.org/team/project/src/main/a.rs#lines-7".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_commit_urls() {
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").commit_url(sha),
+            Some(format!("https://github.com/zhanba/pji/commit/{sha}"))
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").commit_url(sha),
+            Some(format!("https://gitlab.com/group/project/-/commit/{sha}"))
+        );
+        assert_eq!(
+            repository("git@bitbucket.org:team/project.git").commit_url(sha),
+            Some(format!("https://bitbucket.org/team/project/commits/{sha}"))
+        );
+    }
+
+    #[test]
+    fn builds_latest_release_urls() {
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").latest_release_url(),
+            Some("https://github.com/zhanba/pji/releases/latest".to_string())
+        );
+        assert_eq!(
+            repository("https://gitlab.com/gitlab-org/gitlab.git").latest_release_url(),
+            Some("https://gitlab.com/gitlab-org/gitlab/-/releases/permalink/latest".to_string())
+        );
+        assert_eq!(
+            repository("git@example.com:zhanba/pji.git").latest_release_url(),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_compare_urls_between_branches() {
+        let mut gitea = repository("git@git.example.com:infra/deploy.git");
+        gitea.provider = Some(Provider::Gitea);
+        assert_eq!(
+            gitea.compare_url("main", "feature"),
+            Some("https://git.example.com/infra/deploy/compare/main...feature".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").compare_url("main", "fix"),
+            Some("https://gitlab.com/group/project/-/compare/main...fix".to_string())
+        );
+        assert_eq!(
+            repository("git@bitbucket.org:team/app.git").compare_url("main", "fix"),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_branch_pull_request_urls() {
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").branch_pull_request_url("feat/open-pr"),
+            Some("https://github.com/zhanba/pji/pull/new/feat/open-pr".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:group/project.git").branch_pull_request_url("fix"),
+            Some(
+                "https://gitlab.com/group/project/-/merge_requests/new?merge_request%5Bsource_branch%5D=fix"
+                    .to_string()
+            )
+        );
+        let mut gitea = repository("git@git.example.com:infra/deploy.git");
+        gitea.provider = Some(Provider::Gitea);
+        assert_eq!(gitea.branch_pull_request_url("fix"), None);
+    }
+
+    #[test]
+    fn builds_release_diff_compare_urls() {
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").compare_url("v0.2.0", "main"),
+            Some("https://github.com/zhanba/pji/compare/v0.2.0...main".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:gitlab-org/gitlab.git").compare_url("v17.0.0", "master"),
+            Some("https://gitlab.com/gitlab-org/gitlab/-/compare/v17.0.0...master".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_pull_request_file_anchor() {
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").pull_request_file_url(12, "src/api.rs"),
+            Some(
+                "https://github.com/zhanba/pji/pull/12/files#diff-adea2445327e39eb215de697b440dbbc02135357380fb612a10a9e659129a46b"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            repository("git@gitlab.com:gitlab-org/gitlab.git").pull_request_file_url(1, "a"),
+            None
+        );
+    }
+
+    #[test]
+    fn builds_file_commits_urls_on_default_branch() {
+        assert_eq!(
+            repository("git@github.com:zhanba/pji.git").file_commits_url("main", "src/api.rs"),
+            Some("https://github.com/zhanba/pji/commits/main/src/api.rs".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:gitlab-org/gitlab.git")
+                .file_commits_url("master", "README.md"),
+            Some("https://gitlab.com/gitlab-org/gitlab/-/commits/master/README.md".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_org_urls() {
+        let repo = repository("https://github.com/rust-lang/cargo.git");
+        assert_eq!(
+            repo.org_projects_url(),
+            Some("https://github.com/orgs/rust-lang/projects".to_string())
+        );
+        assert_eq!(
+            repo.org_teams_url(),
+            Some("https://github.com/orgs/rust-lang/teams".to_string())
+        );
+        assert_eq!(
+            repository("git@gitlab.com:gitlab-org/gitlab.git").org_teams_url(),
+            None
+        );
+    }
+
+    fn pji_with_repos(urls: &[&str]) -> Pji {
+        let mut metadata = PjiMetadata::default();
+        for url in urls {
+            metadata.add_repo(
+                &PjiRepo::try_new(url, Path::new("/tmp/pji"), Layout::default()).unwrap(),
+            );
+        }
+        Pji::new(PjiConfig::default(), metadata)
+    }
+
+    #[test]
+    fn worktree_base_keeps_host_and_owner_apart() {
+        let mut metadata = PjiMetadata::default();
+        for url in [
+            "git@github.com:zhanba/pji.git",
+            "git@gitlab.com:zhanba/pji.git",
+            "git@gitlab.com:group/sub/pji.git",
+        ] {
+            metadata.add_repo(
+                &PjiRepo::try_new(url, Path::new("/tmp/pji"), Layout::default()).unwrap(),
+            );
+        }
+        let config = PjiConfig {
+            worktree_base: Some(PathBuf::from("/home/me/worktrees")),
+            ..PjiConfig::default()
+        };
+        let pji = Pji::new(config, metadata);
+        let path = |dir: &str| pji.default_worktree_path(dir, "feature/login");
+
+        assert_eq!(
+            path("/tmp/pji/github.com/zhanba/pji"),
+            Path::new("/home/me/worktrees/github.com/zhanba/pji/feature-login")
+        );
+        assert_eq!(
+            path("/tmp/pji/gitlab.com/zhanba/pji"),
+            Path::new("/home/me/worktrees/gitlab.com/zhanba/pji/feature-login")
+        );
+        assert_eq!(
+            path("/tmp/pji/gitlab.com/group/sub/pji"),
+            Path::new("/home/me/worktrees/gitlab.com/group/sub/pji/feature-login")
+        );
+        assert_eq!(
+            path("/elsewhere/pji"),
+            Path::new("/home/me/worktrees/pji/feature-login")
+        );
+    }
+
+    #[test]
+    fn query_resolves_and_unregisters_intended_repository() {
+        let mut pji = pji_with_repos(&[
+            "git@github.com:zhanba/pji.git",
+            "git@github.com:popomore/projj.git",
+        ]);
+
+        let matches = pji.find_repositories("zhanba");
+        assert_eq!(matches.len(), 1);
+        pji.unregister_repository_dir(&matches[0].dir).unwrap();
+
+        let remaining = pji.repositories();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].git.name, "projj");
+        assert!(pji.unregister_repository_dir(&matches[0].dir).is_err());
+    }
+
+    #[test]
+    fn counts_repositories_per_tag() {
+        let mut pji = pji_with_repos(&[
+            "git@github.com:zhanba/pji.git",
+            "git@github.com:zhanba/dotfiles.git",
+            "git@github.com:popomore/projj.git",
+        ]);
+        let tags = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        pji.add_repository_tags("/tmp/pji/github.com/zhanba/pji", &tags(&["rust", "work"]))
+            .unwrap();
+        pji.add_repository_tags("/tmp/pji/github.com/zhanba/dotfiles", &tags(&["work"]))
+            .unwrap();
+
+        assert_eq!(
+            pji.tags().into_iter().collect::<Vec<_>>(),
+            vec![("rust".to_string(), 1), ("work".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn setting_default_root_moves_it_first() {
+        let mut pji = pji_with_repos(&[]);
+        pji.config.roots = vec![PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b")];
+
+        pji.set_default_root("/tmp/b").unwrap();
+        assert_eq!(
+            pji.roots(),
+            &[PathBuf::from("/tmp/b"), PathBuf::from("/tmp/a")]
+        );
+        assert!(matches!(
+            pji.set_default_root("/tmp/c"),
+            Err(PjiError::RootNotConfigured(_))
+        ));
+    }
+
     #[test]
     fn identifies_worktree_dirs_by_name() {
         assert!(is_worktree_dir(Path::new(
@@ -581,4 +3357,53 @@ mod tests {
             "/tmp/pji/github.com/zhanba/worktree-tools"
         )));
     }
+
+    #[test]
+    fn removes_the_checkout_of_a_clone_killed_at_the_timeout() {
+        let base = std::env::temp_dir().join(format!("pji-clone-timeout-{}", std::process::id()));
+        let source = base.join("source");
+        let dir = base.join("github.com/zhanba/pji");
+        std::fs::create_dir_all(&source).unwrap();
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&source)
+            .args(["init", "-q"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        std::fs::create_dir_all(dir.parent().unwrap()).unwrap();
+        let git = GitRunner::new(Duration::from_secs(5), Duration::from_millis(500));
+        let mut command = Command::new("git");
+        command
+            .args(["clone", "--upload-pack", "sleep 5; git-upload-pack"])
+            .arg(format!("file://{}", source.display()))
+            .arg(&dir);
+
+        let result = removing_failed_clone(&dir, || {
+            git.clone_output(&mut command)?;
+            Ok(())
+        });
+        let left_behind = dir.exists();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert!(
+            matches!(result, Err(PjiError::Io(err)) if err.kind() == std::io::ErrorKind::TimedOut)
+        );
+        assert!(!left_behind);
+    }
+
+    #[test]
+    fn in_parallel_keeps_the_input_order() {
+        let items = (0..50u64).collect::<Vec<_>>();
+        // Earlier items take longest, so workers finish in reverse order.
+        let results = in_parallel(&items, |item| {
+            thread::sleep(std::time::Duration::from_millis(50 - item));
+            item * 10
+        });
+        assert_eq!(
+            results,
+            items.iter().map(|item| item * 10).collect::<Vec<_>>()
+        );
+        assert!(in_parallel(&[] as &[u64], |item| *item).is_empty());
+    }
 }
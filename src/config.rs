@@ -1,25 +1,94 @@
 use confy::{get_configuration_file_path, ConfyError};
 use directories::UserDirs;
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
+    api::{Layout, ListColumn},
     constant::{
-        APP_CONFIG_NAME, APP_DATA_NAME, APP_METADATA_VERSION_V1, APP_NAME, DEFAULT_WORKSPACE_NAME,
+        APP_CONFIG_NAME, APP_DATA_NAME, APP_METADATA_VERSION_V1, APP_METADATA_VERSION_V2,
+        APP_METADATA_VERSION_V3, APP_NAME, DEFAULT_WORKSPACE_NAME,
     },
     error::PjiError,
+    provider::Provider,
     repo::PjiRepo,
+    util::redact_url_credentials,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct PjiConfig {
     pub roots: Vec<PathBuf>,
+    /// Where `pji add` clones repositories inside a root and where `pji scan`
+    /// looks for them.
+    #[serde(default)]
+    pub layout: Layout,
+    /// Columns shown by `pji list` without `--long`; empty prints only paths.
+    #[serde(default)]
+    pub list_columns: Vec<ListColumn>,
+    /// Command template used by `pji open`, e.g. `chrome --profile-directory={profile} {url}`.
+    #[serde(default)]
+    pub browser: Option<String>,
+    /// Seconds before a git metadata command (config, worktree, branch) is killed.
+    #[serde(default)]
+    pub git_timeout: Option<u64>,
+    /// Seconds before `git clone` is killed.
+    #[serde(default)]
+    pub clone_timeout: Option<u64>,
+    /// Terminal git UI launched by `pji find --tui`; defaults to `lazygit`.
+    #[serde(default)]
+    pub tui: Option<String>,
+    /// Editor launched by `pji find --editor`; defaults to `$VISUAL`, then `$EDITOR`.
+    #[serde(default)]
+    pub editor: Option<String>,
+    /// Command that opens a new terminal window for `pji find --terminal`.
+    #[serde(default)]
+    pub terminal: Option<String>,
+    /// Directory holding new worktrees as
+    /// `{worktree_base}/{host}/{owner}/{repo}/{branch}` instead of a
+    /// `{repo}.worktrees` sibling of the repository.
+    #[serde(default)]
+    pub worktree_base: Option<PathBuf>,
+    /// Hosting provider for hosts pji can't recognize, e.g. self-hosted servers.
+    #[serde(default)]
+    pub providers: BTreeMap<String, Provider>,
+    /// Real hosts of SSH host aliases from `~/.ssh/config`, e.g.
+    /// `github-work -> github.com`, used to build web URLs.
+    #[serde(default)]
+    pub host_aliases: BTreeMap<String, String>,
+    /// Rewrite GitHub/GitLab URLs passed to `pji add` to SSH when an SSH key or
+    /// agent is available, and to HTTPS otherwise.
+    #[serde(default)]
+    pub auto_protocol: bool,
+    /// Command run by a bare `pji`, e.g. `list --long`; defaults to `find`.
+    #[serde(default)]
+    pub default_command: Option<String>,
+    /// Clone submodules in every `pji add`, as if `--recurse-submodules` was passed.
+    #[serde(default)]
+    pub clone_recurse_submodules: bool,
 }
 
 impl Default for PjiConfig {
     fn default() -> Self {
         Self {
             roots: vec![Self::fallback_default_root()],
+            layout: Layout::default(),
+            list_columns: Vec::new(),
+            browser: None,
+            git_timeout: None,
+            clone_timeout: None,
+            tui: None,
+            editor: None,
+            terminal: None,
+            worktree_base: None,
+            providers: BTreeMap::new(),
+            host_aliases: BTreeMap::new(),
+            auto_protocol: false,
+            default_command: None,
+            clone_recurse_submodules: false,
         }
     }
 }
@@ -29,6 +98,12 @@ impl PjiConfig {
         confy::load_path(config_file_path(APP_CONFIG_NAME)?)
     }
 
+    /// Like `try_load`, but a config that isn't valid TOML is moved aside and
+    /// replaced by the default. Returns the backup path and parse error if so.
+    pub(crate) fn load_or_back_up() -> Result<(Self, Option<(PathBuf, String)>), ConfyError> {
+        load_or_back_up(config_file_path(APP_CONFIG_NAME)?)
+    }
+
     pub(crate) fn get_config_file_path() -> Result<PathBuf, ConfyError> {
         config_file_path(APP_CONFIG_NAME)
     }
@@ -47,6 +122,29 @@ impl PjiConfig {
     pub(crate) fn save(&self) -> Result<(), ConfyError> {
         confy::store_path(config_file_path(APP_CONFIG_NAME)?, self)
     }
+
+    /// Returns the configured root that contains `path`, if any. Both sides are
+    /// canonicalized when they exist, so symlinks into a root count as inside it.
+    /// With nested roots the innermost one wins.
+    pub(crate) fn is_under_root(&self, path: &Path) -> Option<&PathBuf> {
+        let path = canonical(path);
+        self.roots
+            .iter()
+            .map(|root| (root, canonical(root)))
+            .filter(|(_, canonical_root)| path.starts_with(canonical_root))
+            .max_by_key(|(_, canonical_root)| canonical_root.components().count())
+            .map(|(root, _)| root)
+    }
+
+    /// Returns the configured root that `path` refers to, if it is one.
+    pub(crate) fn configured_root(&self, path: &Path) -> Option<&PathBuf> {
+        self.is_under_root(path)
+            .filter(|root| canonical(root) == canonical(path))
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,15 +156,46 @@ pub(crate) struct PjiMetadata {
 impl Default for PjiMetadata {
     fn default() -> Self {
         Self {
-            version: APP_METADATA_VERSION_V1.to_string(),
+            version: APP_METADATA_VERSION_V3.to_string(),
             repos: vec![],
         }
     }
 }
 
 impl PjiMetadata {
-    pub(crate) fn try_load() -> Result<Self, ConfyError> {
-        confy::load_path(config_file_path(APP_DATA_NAME)?)
+    pub(crate) fn try_load() -> Result<Self, PjiError> {
+        let path = config_file_path(APP_DATA_NAME).map_err(PjiError::Metadata)?;
+        let mut metadata: Self = confy::load_path(path).map_err(PjiError::Metadata)?;
+        if metadata.migrate()? | metadata.deduplicate() {
+            metadata.try_save().map_err(PjiError::Metadata)?;
+        }
+        Ok(metadata)
+    }
+
+    pub(crate) fn load_or_back_up() -> Result<(Self, Option<(PathBuf, String)>), PjiError> {
+        let path = config_file_path(APP_DATA_NAME).map_err(PjiError::Metadata)?;
+        let (mut metadata, backup): (Self, _) =
+            load_or_back_up(path).map_err(PjiError::Metadata)?;
+        if metadata.migrate()? | metadata.deduplicate() {
+            metadata.try_save().map_err(PjiError::Metadata)?;
+        }
+        Ok((metadata, backup))
+    }
+
+    /// Upgrades metadata written by an older pji to the current version.
+    /// Fields added since are filled by their serde defaults when parsing, so
+    /// this only needs to bump the version. Returns whether anything changed.
+    /// Versions this pji doesn't know are refused rather than downgraded, since
+    /// saving would drop whatever a newer pji added.
+    pub(crate) fn migrate(&mut self) -> Result<bool, PjiError> {
+        match self.version.as_str() {
+            APP_METADATA_VERSION_V3 => Ok(false),
+            APP_METADATA_VERSION_V1 | APP_METADATA_VERSION_V2 => {
+                self.version = APP_METADATA_VERSION_V3.to_string();
+                Ok(true)
+            }
+            version => Err(PjiError::UnsupportedMetadataVersion(version.to_string())),
+        }
     }
 
     pub(crate) fn try_save(&self) -> Result<(), ConfyError> {
@@ -77,26 +206,399 @@ impl PjiMetadata {
         config_file_path(APP_DATA_NAME)
     }
 
+    /// Tracks `pj_repo`. The same repository already tracked in its
+    /// directory is merged into the existing entry instead of added twice.
     pub(crate) fn add_repo(&mut self, pj_repo: &PjiRepo) -> &mut Self {
-        self.repos.push(pj_repo.clone());
+        match self
+            .repos
+            .iter_mut()
+            .find(|repo| is_same_entry(repo, pj_repo))
+        {
+            Some(existing) => merge_duplicate(existing, pj_repo.clone()),
+            None => self.repos.push(pj_repo.clone()),
+        }
         self
     }
 
     pub(crate) fn remove_repo(&mut self, pj_repo: &PjiRepo) -> &mut Self {
-        self.repos.retain(|repo| repo.dir != pj_repo.dir);
+        self.repos.retain(|repo| !is_same_entry(repo, pj_repo));
         self
     }
 
-    pub(crate) fn has_repo(&self, pj_repo: &PjiRepo) -> bool {
-        self.repos.iter().any(|repo| repo.dir == pj_repo.dir)
+    /// Whether `pj_repo` is tracked. Errors when its directory is tracked for
+    /// another repository, as happens with the flat layout when two owners
+    /// have a repository of the same name.
+    pub(crate) fn has_repo(&self, pj_repo: &PjiRepo) -> Result<bool, PjiError> {
+        if self.repos.iter().any(|repo| is_same_entry(repo, pj_repo)) {
+            return Ok(true);
+        }
+        match self.repos.iter().find(|repo| repo.dir == pj_repo.dir) {
+            Some(other) => Err(PjiError::DirectoryTrackedForOtherRepository {
+                dir: other.dir.clone(),
+                uri: redact_url_credentials(&other.git_uri.uri),
+            }),
+            None => Ok(false),
+        }
     }
 
-    pub(crate) fn deduplicate(&mut self) {
-        let mut seen = std::collections::HashSet::new();
-        self.repos.retain(|repo| seen.insert(repo.dir.clone()));
+    /// Collapses entries for the same repository in one directory, keeping
+    /// the most recent open time. Different repositories sharing a directory
+    /// are both kept; see [`PjiMetadata::directory_conflicts`]. Returns
+    /// whether any entries were dropped.
+    pub(crate) fn deduplicate(&mut self) -> bool {
+        let count = self.repos.len();
+        let mut repos: Vec<PjiRepo> = Vec::with_capacity(count);
+        for repo in self.repos.drain(..) {
+            match repos.iter_mut().find(|kept| is_same_entry(kept, &repo)) {
+                Some(kept) => merge_duplicate(kept, repo),
+                None => repos.push(repo),
+            }
+        }
+        self.repos = repos;
+        self.repos.len() != count
+    }
+
+    /// Directories tracked for more than one repository, with the URLs of
+    /// each. Metadata written before pji refused such entries can hold them.
+    pub(crate) fn directory_conflicts(&self) -> Vec<(PathBuf, Vec<String>)> {
+        let mut uris_by_dir: BTreeMap<&Path, Vec<String>> = BTreeMap::new();
+        for repo in &self.repos {
+            uris_by_dir
+                .entry(&repo.dir)
+                .or_default()
+                .push(redact_url_credentials(&repo.git_uri.uri));
+        }
+        uris_by_dir
+            .into_iter()
+            .filter(|(_, uris)| uris.len() > 1)
+            .map(|(dir, uris)| (dir.to_path_buf(), uris))
+            .collect()
+    }
+}
+
+/// Whether `a` and `b` track the same repository in the same directory.
+fn is_same_entry(a: &PjiRepo, b: &PjiRepo) -> bool {
+    a.dir == b.dir && a.git_uri.same_repository(&b.git_uri)
+}
+
+/// Folds `duplicate` into `kept`: the newer open time wins, while the
+/// creation time and open count keep the oldest and largest values, and the
+/// tags of both, any provider override and the default branch are kept.
+fn merge_duplicate(kept: &mut PjiRepo, duplicate: PjiRepo) {
+    kept.create_time = kept.create_time.min(duplicate.create_time);
+    kept.open_count = kept.open_count.max(duplicate.open_count);
+    kept.add_tags(duplicate.tags.iter().map(String::as_str));
+    kept.provider = kept.provider.or(duplicate.provider);
+    kept.default_branch = kept.default_branch.take().or(duplicate.default_branch);
+    if duplicate.last_open_time > kept.last_open_time {
+        kept.last_open_time = duplicate.last_open_time;
+        kept.git_uri = duplicate.git_uri;
     }
 }
 
 fn config_file_path(config_name: &str) -> Result<PathBuf, ConfyError> {
     get_configuration_file_path(APP_NAME, config_name)
 }
+
+/// Loads `path`, or renames it to `<path>.bak` and returns the default when it
+/// can't be parsed, so one bad byte doesn't break every command.
+fn load_or_back_up<T>(path: PathBuf) -> Result<(T, Option<(PathBuf, String)>), ConfyError>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    match confy::load_path(&path) {
+        Ok(value) => Ok((value, None)),
+        Err(ConfyError::BadTomlData(err)) => {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            let backup = PathBuf::from(backup);
+            fs::rename(&path, &backup).map_err(ConfyError::GeneralLoadError)?;
+            Ok((T::default(), Some((backup, err.to_string()))))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_up_unparsable_files_and_falls_back_to_default() {
+        let dir = std::env::temp_dir().join(format!("pji-corrupt-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("metadata.toml");
+        fs::write(&path, "version = \"1\"\nrepos = [\n").unwrap();
+
+        let (metadata, backup) = load_or_back_up::<PjiMetadata>(path.clone()).unwrap();
+        let backup_contents = fs::read_to_string(dir.join("metadata.toml.bak"));
+        let still_exists = path.exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(metadata.repos.is_empty());
+        assert_eq!(backup.unwrap().0, dir.join("metadata.toml.bak"));
+        assert_eq!(backup_contents.unwrap(), "version = \"1\"\nrepos = [\n");
+        assert!(!still_exists);
+    }
+
+    #[test]
+    fn migrates_v1_metadata_without_open_counts() {
+        let dir = std::env::temp_dir().join(format!("pji-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.toml");
+        fs::write(
+            &path,
+            r#"version = "0.1.0"
+
+[[repos]]
+dir = "/tmp/pji/github.com/zhanba/pji"
+root = "/tmp/pji"
+create_time = "2024-01-01T00:00:00Z"
+last_open_time = "2024-01-02T00:00:00Z"
+
+[repos.git_uri]
+hostname = "github.com"
+user = "zhanba"
+repo = "pji"
+protocol = "SSH"
+uri = "git@github.com:zhanba/pji.git"
+"#,
+        )
+        .unwrap();
+
+        let loaded = load_or_back_up::<PjiMetadata>(path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (mut metadata, backup) = loaded.unwrap();
+        assert!(backup.is_none());
+        assert!(metadata.migrate().unwrap());
+        assert_eq!(metadata.version, APP_METADATA_VERSION_V3);
+        assert_eq!(metadata.repos.len(), 1);
+        assert_eq!(metadata.repos[0].open_count, 0);
+        assert!(metadata.repos[0].tags.is_empty());
+        assert!(metadata.repos[0].provider.is_none());
+        assert!(metadata.repos[0].default_branch.is_none());
+        assert!(!metadata.migrate().unwrap());
+
+        metadata.version = APP_METADATA_VERSION_V2.to_string();
+        assert!(metadata.migrate().unwrap());
+        assert_eq!(metadata.version, APP_METADATA_VERSION_V3);
+    }
+
+    #[test]
+    fn refuses_metadata_from_an_unknown_version() {
+        let mut metadata = PjiMetadata {
+            version: "9.0.0".to_string(),
+            repos: Vec::new(),
+        };
+        assert!(matches!(
+            metadata.migrate(),
+            Err(PjiError::UnsupportedMetadataVersion(version)) if version == "9.0.0"
+        ));
+        assert_eq!(metadata.version, "9.0.0");
+    }
+
+    #[test]
+    fn loads_metadata_with_an_unknown_protocol() {
+        let dir = std::env::temp_dir().join(format!("pji-protocol-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.toml");
+        fs::write(
+            &path,
+            r#"version = "0.2.0"
+
+[[repos]]
+dir = "/tmp/pji/example.com/team/app"
+root = "/tmp/pji"
+create_time = "2024-01-01T00:00:00Z"
+last_open_time = "2024-01-02T00:00:00Z"
+
+[repos.git_uri]
+hostname = "example.com"
+user = "team"
+repo = "app"
+protocol = "QUIC"
+uri = "quic://example.com/team/app.git"
+"#,
+        )
+        .unwrap();
+
+        let (metadata, backup) = load_or_back_up::<PjiMetadata>(path.clone()).unwrap();
+        confy::store_path(&path, &metadata).unwrap();
+        let reloaded = load_or_back_up::<PjiMetadata>(path.clone());
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(backup.is_none());
+        assert_eq!(metadata.repos.len(), 1);
+        assert_eq!(
+            metadata.repos[0].git_uri.protocol,
+            crate::repo::GitProtocol::Unknown("QUIC".to_string())
+        );
+        assert!(saved.contains(r#"protocol = "QUIC""#));
+        let (reloaded, _) = reloaded.unwrap();
+        assert_eq!(reloaded.repos[0].git_uri, metadata.repos[0].git_uri);
+    }
+
+    #[test]
+    fn adding_the_same_repo_twice_keeps_one_entry() {
+        let root = Path::new("/tmp/pji");
+        let first =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", root, Layout::default()).unwrap();
+        let mut second =
+            PjiRepo::try_new("https://github.com/zhanba/pji.git", root, Layout::default()).unwrap();
+        second.last_open_time = first.last_open_time + chrono::Duration::hours(1);
+        let mut metadata = PjiMetadata::default();
+
+        metadata.add_repo(&first).add_repo(&second);
+
+        assert_eq!(metadata.repos.len(), 1);
+        assert_eq!(metadata.repos[0].last_open_time, second.last_open_time);
+        assert_eq!(metadata.repos[0].create_time, first.create_time);
+    }
+
+    #[test]
+    fn deduplicate_keeps_the_newest_open_time() {
+        let root = Path::new("/tmp/pji");
+        let mut newest =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", root, Layout::default()).unwrap();
+        newest.last_open_time += chrono::Duration::hours(1);
+        let older =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", root, Layout::default()).unwrap();
+        let other =
+            PjiRepo::try_new("git@github.com:zhanba/other.git", root, Layout::default()).unwrap();
+        let mut metadata = PjiMetadata {
+            repos: vec![older, other, newest.clone()],
+            ..PjiMetadata::default()
+        };
+
+        assert!(metadata.deduplicate());
+        assert!(!metadata.deduplicate());
+        assert_eq!(metadata.repos.len(), 2);
+        assert_eq!(metadata.repos[0].last_open_time, newest.last_open_time);
+    }
+
+    #[test]
+    fn refuses_two_repositories_in_one_flat_directory() {
+        let root = Path::new("/tmp/pji");
+        let github = PjiRepo::try_new("git@github.com:zhanba/pji.git", root, Layout::Flat).unwrap();
+        let gitlab =
+            PjiRepo::try_new("https://gitlab.com/fork/pji.git", root, Layout::Flat).unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&github);
+
+        assert!(metadata.has_repo(&github).unwrap());
+        assert!(matches!(
+            metadata.has_repo(&gitlab),
+            Err(PjiError::DirectoryTrackedForOtherRepository { .. })
+        ));
+
+        // Metadata written before the check can already hold both; loading
+        // keeps them and reports the directory instead of failing.
+        metadata.repos.push(gitlab);
+        assert!(!metadata.deduplicate());
+        assert_eq!(metadata.repos.len(), 2);
+        assert_eq!(
+            metadata.directory_conflicts(),
+            vec![(
+                PathBuf::from("/tmp/pji/pji"),
+                vec![
+                    "git@github.com:zhanba/pji.git".to_string(),
+                    "https://gitlab.com/fork/pji.git".to_string()
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn tags_round_trip_through_the_metadata_file() {
+        let dir = std::env::temp_dir().join(format!("pji-tags-{}", std::process::id()));
+        let path = dir.join("data.toml");
+        let mut repo =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", &dir, Layout::default()).unwrap();
+        repo.add_tags(["work", "oss"]);
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&repo);
+
+        confy::store_path(&path, &metadata).unwrap();
+        let loaded = load_or_back_up::<PjiMetadata>(path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (metadata, _) = loaded.unwrap();
+        assert_eq!(metadata.repos[0].tags, ["work", "oss"]);
+    }
+
+    #[test]
+    fn remove_repo_leaves_other_repos_intact() {
+        let root = Path::new("/tmp/pji");
+        let pji =
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", root, Layout::default()).unwrap();
+        let other =
+            PjiRepo::try_new("git@github.com:zhanba/other.git", root, Layout::default()).unwrap();
+        let mut metadata = PjiMetadata::default();
+        metadata.add_repo(&pji).add_repo(&other);
+
+        metadata.remove_repo(&pji);
+
+        assert!(!metadata.has_repo(&pji).unwrap());
+        assert!(metadata.has_repo(&other).unwrap());
+        assert_eq!(metadata.repos.len(), 1);
+    }
+
+    #[test]
+    fn finds_the_root_containing_a_path() {
+        let base = std::env::temp_dir().join(format!("pji-under-root-{}", std::process::id()));
+        let root = base.join("root");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(root.join("github.com/zhanba/pji")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+
+        let config = PjiConfig {
+            roots: vec![root.clone()],
+            ..PjiConfig::default()
+        };
+        assert_eq!(
+            config.is_under_root(&root.join("github.com/zhanba/pji")),
+            Some(&root)
+        );
+        assert_eq!(
+            config.is_under_root(&root.join("not/created/yet")),
+            Some(&root)
+        );
+        assert_eq!(config.is_under_root(&outside), None);
+
+        #[cfg(unix)]
+        {
+            let link = outside.join("pji");
+            std::os::unix::fs::symlink(root.join("github.com/zhanba/pji"), &link).unwrap();
+            assert_eq!(config.is_under_root(&link), Some(&root));
+        }
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn matches_configured_roots_through_symlinks() {
+        let base = std::env::temp_dir().join(format!("pji-configured-root-{}", std::process::id()));
+        let root = base.join("root");
+        let nested = root.join("work");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let config = PjiConfig {
+            roots: vec![root.clone(), nested.clone()],
+            ..PjiConfig::default()
+        };
+        assert_eq!(config.is_under_root(&nested.join("pji")), Some(&nested));
+        assert_eq!(config.configured_root(&root), Some(&root));
+        assert_eq!(config.configured_root(&root.join("github.com")), None);
+
+        #[cfg(unix)]
+        {
+            let link = base.join("link");
+            std::os::unix::fs::symlink(&root, &link).unwrap();
+            assert_eq!(config.configured_root(&link), Some(&root));
+        }
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
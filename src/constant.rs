@@ -1,5 +1,12 @@
 pub const APP_NAME: &str = "pji";
 pub const APP_CONFIG_NAME: &str = "config";
 pub const APP_DATA_NAME: &str = "data";
+pub const APP_GIT_LOG_NAME: &str = "git.log";
+pub const GIT_LOG_ENV: &str = "PJI_LOG";
 pub const DEFAULT_WORKSPACE_NAME: &str = "pji";
 pub const APP_METADATA_VERSION_V1: &str = "0.1.0";
+/// Adds `open_count` to every repository.
+pub const APP_METADATA_VERSION_V2: &str = "0.2.0";
+/// Adds optional per-repository `tags`, `provider`, and `default_branch`.
+pub const APP_METADATA_VERSION_V3: &str = "0.3.0";
+pub const DEFAULT_TUI_COMMAND: &str = "lazygit";
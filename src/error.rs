@@ -16,6 +16,10 @@ pub enum PjiError {
     Io(#[from] io::Error),
     #[error("repository is already registered: {}", .0.display())]
     RepositoryAlreadyRegistered(PathBuf),
+    #[error("root is not configured: {}", .0.display())]
+    RootNotConfigured(PathBuf),
+    #[error("root still has tracked repositories ({count}): {}", root.display())]
+    RootHasRepositories { root: PathBuf, count: usize },
     #[error("repository is not registered: {}", .0.display())]
     RepositoryNotRegistered(PathBuf),
     #[error("git command failed (`{command}`): {}", stderr.trim())]
@@ -24,4 +28,16 @@ pub enum PjiError {
     EmptyGitOutput { command: String },
     #[error("invalid worktree: {0}")]
     InvalidWorktree(String),
+    #[error("'{}' is already a checkout of another repository (origin: {origin})", dir.display())]
+    DirectoryHasOtherOrigin { dir: PathBuf, origin: String },
+    #[error("'{}' is already tracked for another repository ({uri})", dir.display())]
+    DirectoryTrackedForOtherRepository { dir: PathBuf, uri: String },
+    #[error("destination already exists: {}", .0.display())]
+    DestinationExists(PathBuf),
+    #[error(
+        "pji metadata version '{0}' is not supported; it may have been written by a newer pji"
+    )]
+    UnsupportedMetadataVersion(String),
+    #[error("invalid pji export: {0}")]
+    InvalidExport(#[source] serde_json::Error),
 }
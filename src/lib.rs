@@ -2,12 +2,16 @@ mod api;
 mod config;
 mod constant;
 mod error;
+mod git;
+mod provider;
 mod repo;
 mod util;
 mod worktree;
 
 pub use api::{
-    AddWorktreeRequest, GitUrl, Pji, Protocol, RemoveWorktreeRequest, Repository, ScanIssue,
-    ScanReport, Worktree, WorktreeList,
+    AddWorktreeRequest, CloneOptions, GitUrl, ImportOutcome, Layout, ListColumn, Pji, Protocol,
+    RecoveredFile, RemoveWorktreeRequest, Repository, RepositoryOrder, RepositoryStatus,
+    RepositorySync, RootScan, ScanIssue, ScanReport, SyncOutcome, Worktree, WorktreeList,
 };
 pub use error::PjiError;
+pub use provider::Provider;
@@ -1,11 +1,16 @@
-use clap::{Args, Parser, Subcommand};
+use anyhow::{anyhow, Context};
+use chrono::{TimeDelta, Utc};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use dialoguer::console::{user_attended, user_attended_stderr};
 use std::io::{self, IsTerminal};
 use std::path::PathBuf;
 
 mod app;
+mod shell;
 
-use app::{AppOptions, PjiApp};
+use app::{AppOptions, ListFormat, OpenWith, PjiApp, RepoFilter};
+use pji::{CloneOptions, Pji, Provider, RepositoryOrder};
+use shell::Shell;
 
 /// A CLI for managing, finding, and opening Git repositories.
 #[derive(Debug, Parser)]
@@ -16,10 +21,18 @@ struct Cli {
     #[arg(short = 'n', long, global = true)]
     non_interactive: bool,
 
-    /// Select a root directory without prompting
+    /// Select a configured root directory without prompting
     #[arg(long, global = true, value_name = "DIR")]
     root: Option<PathBuf>,
 
+    /// Log every git command to a debug log in the pji data directory
+    #[arg(long, global = true)]
+    debug: bool,
+
+    /// Print the URLs of `open` commands instead of launching a browser
+    #[arg(long, global = true)]
+    print_url: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 
@@ -30,19 +43,44 @@ struct Cli {
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Configure the root directory for your repositories
-    Config {
-        /// Root directory to add
-        root: Option<PathBuf>,
-    },
-    /// Add a git repository
+    Config(ConfigArgs),
+    /// Clone a git repository into a root and track it
+    #[command(alias = "clone")]
     Add {
         /// git repository url
         git: String,
+        /// Create a shallow clone with this many commits of history
+        #[arg(long)]
+        depth: Option<u32>,
+        /// Check out this branch instead of the remote's default branch
+        #[arg(short, long)]
+        branch: Option<String>,
+        /// Also clone the repository's submodules
+        #[arg(long)]
+        recurse_submodules: bool,
     },
     /// Remove a git repository
     Remove {
-        /// git repository url
-        git: String,
+        /// git repository url, or a query to fuzzy-select a tracked repository.
+        /// Omit it to pick from all tracked repositories
+        git: Option<String>,
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+        /// Also delete the repository's `{repo}.worktrees` directory if it holds no worktrees
+        #[arg(long, conflicts_with = "keep_files")]
+        purge_worktree_dir: bool,
+        /// Stop tracking the repository but leave its files on disk
+        #[arg(long)]
+        keep_files: bool,
+    },
+    /// Move a repository's directory into another configured root
+    Move {
+        /// Query to fuzzy-select the repository; omit it to pick from all of them
+        query: Option<String>,
+        /// Configured root to move the repository into
+        #[arg(long, value_name = "ROOT")]
+        to: PathBuf,
         /// Skip confirmation prompt
         #[arg(short = 'y', long)]
         yes: bool,
@@ -51,13 +89,151 @@ enum Commands {
     List {
         #[arg(short, long)]
         long: bool,
+        /// Print repositories as a JSON array; takes precedence over `--long` and `--group-by`
+        #[arg(long)]
+        json: bool,
+        /// Only list repositories with a commit within this window, e.g. 12h, 3d, or 2w
+        #[arg(long, value_name = "DURATION", value_parser = parse_window)]
+        active: Option<TimeDelta>,
+        /// Only list repositories tagged TAG
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only list repositories whose directory is missing
+        #[arg(long, conflicts_with = "present")]
+        missing: bool,
+        /// Only list repositories whose directory exists
+        #[arg(long)]
+        present: bool,
+        /// Order by last opened, repository name, or time added
+        #[arg(long, value_enum, default_value_t = ListSort::Recent)]
+        sort: ListSort,
+        /// Reverse the order
+        #[arg(short, long)]
+        reverse: bool,
+        /// Group repositories under each of their tags
+        #[arg(long, value_enum, value_name = "GROUP")]
+        group_by: Option<ListGroup>,
     },
     /// Fuzzy search for git repositories
-    Find { query: Option<String> },
+    Find {
+        query: Option<String>,
+        /// Open the selected repository in the configured terminal git UI
+        #[arg(long, conflicts_with_all = ["editor", "terminal", "exec"])]
+        tui: bool,
+        /// Open the selected repository in the configured editor, `$VISUAL`, or `$EDITOR`
+        #[arg(long, conflicts_with_all = ["terminal", "exec"])]
+        editor: bool,
+        /// Open a new terminal window in the selected repository
+        #[arg(long, conflicts_with = "exec")]
+        terminal: bool,
+        /// Run COMMAND in the selected repository with `sh -c`, e.g. --exec 'git pull'
+        #[arg(long, value_name = "COMMAND")]
+        exec: Option<String>,
+        /// Only offer repositories hosted on HOST, e.g. github.com
+        #[arg(long)]
+        host: Option<String>,
+        /// Only offer repositories owned by USER
+        #[arg(long)]
+        user: Option<String>,
+        /// Only offer repositories tagged TAG
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Print the path of a repository, for use as `cd "$(pji cd QUERY)"`
+    Cd { query: Option<String> },
     /// Scan all git repositories in the root directory and save their information
-    Scan,
-    /// Clean pji metadata and configuration
-    Clean,
+    Scan {
+        /// Set each repository's last opened time to its latest commit date
+        #[arg(long)]
+        update_times: bool,
+        /// Search each root recursively and also register repositories outside
+        /// the host/user/repo layout
+        #[arg(long, visible_alias = "all")]
+        deep: bool,
+    },
+    /// Write the tracked repositories to a JSON file for `pji import`
+    Export {
+        /// Output file, or `-` for stdout
+        file: PathBuf,
+    },
+    /// Clone the repositories listed in a `pji export` file into a root
+    Import {
+        /// File written by `pji export`, or `-` for stdin
+        file: PathBuf,
+    },
+    /// Clone tracked repositories whose directory is missing
+    Sync {
+        /// Also `git pull --ff-only` the repositories that exist
+        #[arg(long)]
+        pull: bool,
+    },
+    /// Delete pji metadata and configuration, after confirmation
+    Clean {
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Show the pji binary, config, metadata, and default root locations
+    Which,
+    /// Check that the clipboard and browser used by pji are available
+    Doctor,
+    /// Show uncommitted changes and commits ahead/behind upstream for every repository
+    Status,
+    /// Run a command in every tracked repository, e.g. `pji exec -- git fetch`
+    Exec {
+        /// Only run in repositories hosted on HOST, e.g. github.com
+        #[arg(long)]
+        host: Option<String>,
+        /// Only run in repositories owned by USER
+        #[arg(long)]
+        user: Option<String>,
+        /// Only run in repositories tagged TAG
+        #[arg(long)]
+        tag: Option<String>,
+        /// Keep going after the command fails in a repository
+        #[arg(long)]
+        continue_on_error: bool,
+        /// Command and arguments to run
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Check that every tracked repository exists and still has the same origin remote
+    Verify,
+    /// Stop tracking repositories whose directory was deleted
+    Prune {
+        /// Only list the repositories that would be pruned
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Print a shell completion script
+    #[command(hide = true)]
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print a shell function that lets `pji find`, `pji add`, and `pji wt` cd the current shell
+    Init {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// List, add, or remove root directories
+    Root {
+        #[command(subcommand)]
+        command: RootCommands,
+    },
+    /// Inspect a tracked repository
+    Repo {
+        #[command(subcommand)]
+        command: RepoCommands,
+    },
+    /// Label repositories, e.g. `work` or `oss`, to filter `find`, `list`, and `exec`
+    Tag {
+        #[command(subcommand)]
+        command: TagCommands,
+    },
     /// Open a git repository page (e.g., home, PR, issue) in the browser
     Open(OpenArgs),
     /// Manage git worktrees
@@ -65,6 +241,131 @@ enum Commands {
     Worktree(WorktreeArgs),
 }
 
+#[derive(Debug, Args)]
+#[command(flatten_help = true)]
+struct ConfigArgs {
+    #[command(subcommand)]
+    command: Option<ConfigCommands>,
+
+    /// Root directory to add
+    root: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+    /// Make a configured root the preselected choice when picking a root
+    #[command(alias = "set-root-default")]
+    SetDefault {
+        /// Root directory to make the default
+        root: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum RootCommands {
+    /// List the roots with the number of repositories tracked under each
+    List,
+    /// Add a root, creating the directory if needed
+    Add { path: PathBuf },
+    /// Remove a root from the config without deleting any files
+    Remove {
+        path: PathBuf,
+        /// Remove the root even if repositories are still tracked under it
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum RepoCommands {
+    /// Print everything pji knows about a repository, plus its git status
+    Info { query: Option<String> },
+    /// Set the hosting provider of the current repository, or the one matching
+    /// --repo, e.g. for GitHub Enterprise on a custom domain
+    Provider {
+        #[arg(value_enum)]
+        provider: ProviderArg,
+        /// Query selecting the repository instead of the current one
+        #[arg(short, long, value_name = "QUERY")]
+        repo: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ProviderArg {
+    Github,
+    Gitlab,
+    Gitea,
+    Bitbucket,
+    BitbucketServer,
+    /// Clear the override and detect the provider again
+    Auto,
+}
+
+impl From<ProviderArg> for Option<Provider> {
+    fn from(provider: ProviderArg) -> Self {
+        match provider {
+            ProviderArg::Github => Some(Provider::GitHub),
+            ProviderArg::Gitlab => Some(Provider::GitLab),
+            ProviderArg::Gitea => Some(Provider::Gitea),
+            ProviderArg::Bitbucket => Some(Provider::Bitbucket),
+            ProviderArg::BitbucketServer => Some(Provider::BitbucketServer),
+            ProviderArg::Auto => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ListGroup {
+    Tag,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ListSort {
+    Recent,
+    Name,
+    Created,
+}
+
+impl From<ListSort> for RepositoryOrder {
+    fn from(sort: ListSort) -> Self {
+        match sort {
+            ListSort::Recent => Self::Recent,
+            ListSort::Name => Self::Name,
+            ListSort::Created => Self::Created,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum TagCommands {
+    /// Tag the current repository, or the one matching --repo
+    Add {
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Query selecting the repository instead of the current one
+        #[arg(short, long, value_name = "QUERY")]
+        repo: Option<String>,
+    },
+    /// Remove tags from the current repository, or the one matching --repo
+    #[command(alias = "rm")]
+    Remove {
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Query selecting the repository instead of the current one
+        #[arg(short, long, value_name = "QUERY")]
+        repo: Option<String>,
+    },
+    /// Print every tag in use with its number of repositories, or the tags
+    /// of the repository matching --repo
+    #[command(alias = "ls")]
+    List {
+        /// Query selecting a repository to print the tags of
+        #[arg(short, long, value_name = "QUERY")]
+        repo: Option<String>,
+    },
+}
+
 #[derive(Debug, Args)]
 #[command(flatten_help = true)]
 struct WorktreeArgs {
@@ -110,6 +411,26 @@ enum WorktreeCommands {
         #[arg(short = 'y', long)]
         yes: bool,
     },
+    /// Move a worktree to another path
+    #[command(alias = "mv")]
+    Move {
+        /// Path or name of the worktree to move
+        worktree: String,
+        /// New path of the worktree; it must not exist yet
+        new_path: PathBuf,
+    },
+    /// Lock a worktree so git won't prune, move, or remove it
+    Lock {
+        /// Path or name of the worktree to lock
+        worktree: String,
+        /// Why the worktree is locked, shown by `pji worktree list`
+        reason: Option<String>,
+    },
+    /// Unlock a locked worktree
+    Unlock {
+        /// Path or name of the worktree to unlock
+        worktree: String,
+    },
     /// Clean up stale worktree information
     Prune,
 }
@@ -122,6 +443,14 @@ struct OpenArgs {
 
     #[command(flatten)]
     home: OpenHomeArgs,
+
+    /// Browser profile substituted into the configured `browser` command
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Resolve PATH arguments from the repository root instead of the current directory
+    #[arg(long, global = true)]
+    repo_root: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -130,14 +459,98 @@ enum OpenCommands {
     Home(OpenHomeArgs),
     /// open a git repository pull request page in browser
     PR {
-        /// pull request number
+        /// pull request number; defaults to the pull request of the current branch
         number: Option<u32>,
+        /// open the diff of this file, relative to the current directory (or `--repo-root`)
+        #[arg(long, value_name = "PATH", requires = "number")]
+        file: Option<PathBuf>,
+    },
+    /// open an issue or pull request by number in browser
+    Ref {
+        /// issue or pull request number
+        number: u32,
+    },
+    /// open the runs of a GitHub Actions workflow in browser
+    Ci {
+        /// workflow file in .github/workflows, with or without the extension
+        workflow: String,
+    },
+    /// open a search for your pull requests in browser
+    Prs {
+        /// search every repository of the owning organization
+        #[arg(long)]
+        org: bool,
     },
     /// open a git repository issue page in browser
     Issue {
         /// issue number
         number: Option<u32>,
     },
+    /// open the security advisories page in browser
+    Advisory {
+        /// Open the form for drafting a new advisory
+        #[arg(long)]
+        new: bool,
+    },
+    /// open a git repository latest release page in browser
+    Latest,
+    /// open the releases page, or the release of a tag, in browser
+    #[command(alias = "release")]
+    Releases {
+        /// tag of the release to open
+        tag: Option<String>,
+    },
+    /// open the CI runs (GitHub Actions, GitLab pipelines) in browser
+    Actions,
+    /// open the files of a branch in browser
+    Branch {
+        /// branch name. Defaults to the current branch, or HEAD's commit when detached
+        name: Option<String>,
+    },
+    /// open a commit in browser
+    Commit {
+        /// commit sha. Defaults to the current HEAD
+        sha: Option<String>,
+    },
+    /// open the changes between a tag and the current branch in browser
+    ReleaseDiff {
+        /// tag to compare from. Defaults to the most recent tag
+        tag: Option<String>,
+    },
+    /// open the changes between two refs in browser
+    Compare {
+        /// ref to compare against. With only one ref given, it is the head
+        /// and the default branch is the base
+        base: Option<String>,
+        /// ref with the changes. Defaults to the current branch
+        head: Option<String>,
+    },
+    /// open a file on the current branch in browser
+    File {
+        /// file path, relative to the current directory (or `--repo-root`)
+        path: PathBuf,
+        /// line to highlight
+        #[arg(short, long)]
+        line: Option<u32>,
+    },
+    /// open the history of a file on the default branch in browser
+    FileLatest {
+        /// file path, relative to the current directory (or `--repo-root`)
+        path: PathBuf,
+    },
+    /// open a page of the organization that owns the repository in browser
+    Org {
+        #[command(subcommand)]
+        command: OpenOrgCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OpenOrgCommands {
+    /// open the organization projects page
+    Projects,
+    /// open the organization teams page
+    Teams,
 }
 
 #[derive(Debug, Args)]
@@ -148,45 +561,263 @@ struct OpenHomeArgs {
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    // `clean` must work even when the existing files can't be read.
+    if let Some(yes) = clean_request(cli.command.as_ref()) {
+        return PjiApp::clean(yes, !cli.non_interactive && terminal_is_interactive());
+    }
+
+    let (pji, recovered) = Pji::load_or_recover().context("failed to load pji data")?;
+    for file in &recovered {
+        eprintln!(
+            "⚠️  Could not parse '{}'; moved it to '{}' and using defaults.\n{}",
+            file.path.display(),
+            file.backup.display(),
+            file.message.trim_end()
+        );
+    }
+    for conflict in pji.directory_conflicts() {
+        eprintln!(
+            "⚠️  '{}' is {}; untrack the wrong one with `pji remove URL --keep-files`.",
+            conflict.path.display(),
+            conflict.message
+        );
+    }
+    let command = match cli.command {
+        Some(command) => Some(command),
+        None => pji
+            .default_command()
+            .map(|default| parse_default_command(default, cli.query.as_deref()))
+            .transpose()?,
+    };
+    // A `default_command` of `clean` takes the same path, before the app is set up.
+    if let Some(yes) = clean_request(command.as_ref()) {
+        return PjiApp::clean(yes, !cli.non_interactive && terminal_is_interactive());
+    }
+
+    let (browser_profile, paths_from_repo_root) = match &command {
+        Some(Commands::Open(args)) => (args.profile.clone(), args.repo_root),
+        _ => (None, false),
+    };
+    // `pji cd` is meant to run inside `$(...)`, so its stdout is never a terminal.
+    let interactive = match &command {
+        Some(Commands::Cd { .. }) => prompts_are_interactive(),
+        _ => terminal_is_interactive(),
+    };
     let app_options = AppOptions {
-        interactive: !cli.non_interactive && terminal_is_interactive(),
+        interactive: !cli.non_interactive && interactive,
         root: cli.root,
+        browser_profile,
+        paths_from_repo_root,
+        debug: cli.debug,
+        print_url: cli.print_url,
     };
-    let mut app = PjiApp::new(app_options)?;
+    let mut app = PjiApp::new(pji, app_options)?;
 
-    match cli.command {
+    match command {
         Some(command) => match command {
-            Commands::Config { root } => {
-                app.start_config(root)?;
+            Commands::Config(args) => match args.command {
+                Some(ConfigCommands::SetDefault { root }) => {
+                    app.set_default_root(root)?;
+                }
+                None => {
+                    app.start_config(args.root)?;
+                }
+            },
+            Commands::Add {
+                git,
+                depth,
+                branch,
+                recurse_submodules,
+            } => {
+                let options = CloneOptions {
+                    depth,
+                    branch,
+                    recurse_submodules,
+                };
+                app.add(git.as_str(), &options)?;
+            }
+            Commands::Remove {
+                git,
+                yes,
+                purge_worktree_dir,
+                keep_files,
+            } => {
+                app.remove(
+                    git.as_deref().unwrap_or(""),
+                    yes,
+                    purge_worktree_dir,
+                    keep_files,
+                )?;
             }
-            Commands::Add { git } => {
-                app.add(git.as_str())?;
+            Commands::Move { query, to, yes } => {
+                app.move_repo(query.as_deref().unwrap_or(""), &to, yes)?;
             }
-            Commands::Remove { git, yes } => {
-                app.remove(git.as_str(), yes)?;
+            Commands::List {
+                long,
+                json,
+                active,
+                tag,
+                missing,
+                present,
+                sort,
+                reverse,
+                group_by,
+            } => {
+                let present = match (missing, present) {
+                    (true, _) => Some(false),
+                    (_, true) => Some(true),
+                    _ => None,
+                };
+                let filter = RepoFilter {
+                    tag,
+                    present,
+                    ..RepoFilter::default()
+                };
+                let format = ListFormat {
+                    long,
+                    json,
+                    group_by_tag: group_by == Some(ListGroup::Tag),
+                };
+                app.list(&format, active, &filter, sort.into(), reverse)?;
             }
-            Commands::List { long } => {
-                app.list(long)?;
+            Commands::Find {
+                query,
+                tui,
+                editor,
+                terminal,
+                exec,
+                host,
+                user,
+                tag,
+            } => {
+                let open_with = if tui {
+                    OpenWith::Tui
+                } else if editor {
+                    OpenWith::Editor
+                } else if terminal {
+                    OpenWith::Terminal
+                } else if let Some(command) = exec {
+                    OpenWith::Exec(command)
+                } else {
+                    OpenWith::Shell
+                };
+                let filter = RepoFilter {
+                    host,
+                    user,
+                    tag,
+                    ..RepoFilter::default()
+                };
+                app.find(query.as_deref().unwrap_or(""), open_with, &filter)?;
             }
-            Commands::Find { query } => {
-                app.find(query.as_deref().unwrap_or(""))?;
+            Commands::Cd { query } => {
+                app.cd(query.as_deref().unwrap_or(""))?;
             }
-            Commands::Scan => {
-                app.scan()?;
+            Commands::Scan { update_times, deep } => {
+                app.scan(update_times, deep)?;
             }
-            Commands::Clean => PjiApp::clean()?,
+            Commands::Export { file } => app.export(&file)?,
+            Commands::Import { file } => app.import(&file)?,
+            Commands::Sync { pull } => app.sync(pull)?,
+            Commands::Clean { .. } => unreachable!("handled before the app is set up"),
+            Commands::Which => PjiApp::which()?,
+            Commands::Doctor => app.doctor()?,
+            Commands::Status => app.status()?,
+            Commands::Exec {
+                host,
+                user,
+                tag,
+                continue_on_error,
+                command,
+            } => {
+                let filter = RepoFilter {
+                    host,
+                    user,
+                    tag,
+                    ..RepoFilter::default()
+                };
+                app.exec(&command, &filter, continue_on_error)?;
+            }
+            Commands::Verify => app.verify()?,
+            Commands::Prune { dry_run, yes } => app.prune(dry_run, yes)?,
+            Commands::Init { shell } => print!("{}", shell::init_script(shell)),
+            Commands::Completions { shell } => print_completions(shell, &mut io::stdout()),
+            Commands::Root { command } => match command {
+                RootCommands::List => app.root_list()?,
+                RootCommands::Add { path } => app.root_add(path)?,
+                RootCommands::Remove { path, force } => app.root_remove(path, force)?,
+            },
+            Commands::Tag { command } => match command {
+                TagCommands::Add { tags, repo } => app.tag(repo.as_deref(), &tags, &[])?,
+                TagCommands::Remove { tags, repo } => app.tag(repo.as_deref(), &[], &tags)?,
+                TagCommands::List { repo } => app.tag_list(repo.as_deref())?,
+            },
+            Commands::Repo { command } => match command {
+                RepoCommands::Info { query } => {
+                    app.repo_info(query.as_deref().unwrap_or(""))?;
+                }
+                RepoCommands::Provider { provider, repo } => {
+                    app.repo_provider(repo.as_deref(), provider.into())?;
+                }
+            },
             Commands::Open(args) => {
                 let open_cmd = args.command.unwrap_or(OpenCommands::Home(args.home));
                 match open_cmd {
                     OpenCommands::Home(home) => {
                         app.open_home(home.url)?;
                     }
-                    OpenCommands::PR { number } => {
-                        app.open_pr(number)?;
+                    OpenCommands::PR { number, file } => {
+                        app.open_pr(number, file)?;
+                    }
+                    OpenCommands::Ref { number } => {
+                        app.open_ref(number)?;
+                    }
+                    OpenCommands::Ci { workflow } => {
+                        app.open_ci(&workflow)?;
+                    }
+                    OpenCommands::Prs { org } => {
+                        app.open_my_prs(org)?;
                     }
                     OpenCommands::Issue { number } => {
                         app.open_issue(number)?;
                     }
+                    OpenCommands::Advisory { new } => {
+                        app.open_advisories(new)?;
+                    }
+                    OpenCommands::Latest => {
+                        app.open_latest_release()?;
+                    }
+                    OpenCommands::Releases { tag } => {
+                        app.open_releases(tag)?;
+                    }
+                    OpenCommands::Actions => {
+                        app.open_actions()?;
+                    }
+                    OpenCommands::Branch { name } => {
+                        app.open_branch(name)?;
+                    }
+                    OpenCommands::Commit { sha } => {
+                        app.open_commit(sha)?;
+                    }
+                    OpenCommands::ReleaseDiff { tag } => {
+                        app.open_release_diff(tag)?;
+                    }
+                    OpenCommands::Compare { base, head } => {
+                        app.open_compare(base, head)?;
+                    }
+                    OpenCommands::File { path, line } => {
+                        app.open_file(path, line)?;
+                    }
+                    OpenCommands::FileLatest { path } => {
+                        app.open_file_latest(path)?;
+                    }
+                    OpenCommands::Org { command } => match command {
+                        OpenOrgCommands::Projects => {
+                            app.open_org_projects()?;
+                        }
+                        OpenOrgCommands::Teams => {
+                            app.open_org_teams()?;
+                        }
+                    },
                 }
             }
             Commands::Worktree(args) => {
@@ -215,6 +846,15 @@ fn main() -> anyhow::Result<()> {
                     } => {
                         app.worktree_remove(worktree, force, yes)?;
                     }
+                    WorktreeCommands::Move { worktree, new_path } => {
+                        app.worktree_move(&worktree, new_path)?;
+                    }
+                    WorktreeCommands::Lock { worktree, reason } => {
+                        app.worktree_lock(&worktree, reason.as_deref())?;
+                    }
+                    WorktreeCommands::Unlock { worktree } => {
+                        app.worktree_unlock(&worktree)?;
+                    }
                     WorktreeCommands::Prune => {
                         app.worktree_prune()?;
                     }
@@ -223,15 +863,161 @@ fn main() -> anyhow::Result<()> {
         },
         None => {
             // Default to find command when no subcommand is provided
-            app.find(cli.query.as_deref().unwrap_or(""))?;
+            app.find(
+                cli.query.as_deref().unwrap_or(""),
+                OpenWith::Shell,
+                &RepoFilter::default(),
+            )?;
         }
     }
 
     Ok(())
 }
 
+/// The `--yes` flag of a `pji clean`, which runs before the app is set up.
+fn clean_request(command: Option<&Commands>) -> Option<bool> {
+    match command {
+        Some(Commands::Clean { yes }) => Some(*yes),
+        _ => None,
+    }
+}
+
+/// Parses the configured `default_command`, passing a bare `pji QUERY`'s query
+/// on as its last argument.
+fn parse_default_command(default: &str, query: Option<&str>) -> anyhow::Result<Commands> {
+    let args = std::iter::once("pji")
+        .chain(default.split_whitespace())
+        .chain(query);
+    Cli::try_parse_from(args)
+        .with_context(|| format!("invalid `default_command` '{default}' in the pji config"))?
+        .command
+        .ok_or_else(|| anyhow!("`default_command` '{default}' does not name a pji command"))
+}
+
+/// Parses a window such as `12h`, `3d`, or `2w`.
+fn parse_window(value: &str) -> Result<TimeDelta, String> {
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in '{value}'; use h, d, or w"))?;
+    let (amount, unit) = value.split_at(unit_start);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{value}'"))?;
+    let window = match unit {
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        _ => {
+            return Err(format!(
+                "unknown unit '{unit}' in '{value}'; use h, d, or w"
+            ))
+        }
+    };
+    window
+        .filter(|window| Utc::now().checked_sub_signed(*window).is_some())
+        .ok_or_else(|| format!("duration '{value}' is too large"))
+}
+
+fn print_completions(shell: clap_complete::Shell, out: &mut dyn io::Write) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
+}
+
 fn terminal_is_interactive() -> bool {
     // A prompt-driven CLI needs all three streams attached: stdin for input,
     // stdout for shell handoff/output, and stderr for dialoguer prompts.
-    io::stdin().is_terminal() && user_attended() && user_attended_stderr()
+    user_attended() && prompts_are_interactive()
+}
+
+fn prompts_are_interactive() -> bool {
+    io::stdin().is_terminal() && user_attended_stderr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn exec_takes_the_command_after_its_flags() {
+        let cli = Cli::try_parse_from([
+            "pji", "exec", "--user", "zhanba", "--", "git", "fetch", "--all",
+        ])
+        .unwrap();
+        let Some(Commands::Exec {
+            user,
+            continue_on_error,
+            command,
+            ..
+        }) = cli.command
+        else {
+            panic!("expected exec");
+        };
+        assert_eq!(user.as_deref(), Some("zhanba"));
+        assert!(!continue_on_error);
+        assert_eq!(command, ["git", "fetch", "--all"]);
+    }
+
+    #[test]
+    fn remove_accepts_no_target() {
+        let cli = Cli::try_parse_from(["pji", "remove", "-y"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Remove {
+                git: None,
+                yes: true,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn bare_pji_runs_the_configured_default_command() {
+        assert!(matches!(
+            parse_default_command("list --long", None),
+            Ok(Commands::List { long: true, .. })
+        ));
+        assert!(matches!(
+            parse_default_command("find --tui", Some("pji")),
+            Ok(Commands::Find { query: Some(query), tui: true, .. }) if query == "pji"
+        ));
+        assert!(parse_default_command("--debug", None).is_err());
+        assert!(parse_default_command("launch", None).is_err());
+    }
+
+    #[test]
+    fn clean_from_default_command_runs_before_the_app_is_set_up() {
+        let clean = parse_default_command("clean --yes", None).unwrap();
+        assert_eq!(clean_request(Some(&clean)), Some(true));
+        let list = parse_default_command("list", None).unwrap();
+        assert_eq!(clean_request(Some(&list)), None);
+        assert_eq!(clean_request(None), None);
+    }
+
+    #[test]
+    fn parses_activity_windows() {
+        assert_eq!(parse_window("12h"), Ok(TimeDelta::hours(12)));
+        assert_eq!(parse_window("3d"), Ok(TimeDelta::days(3)));
+        assert_eq!(parse_window("2w"), Ok(TimeDelta::weeks(2)));
+        assert!(parse_window("3").is_err());
+        assert!(parse_window("d").is_err());
+        assert!(parse_window("3y").is_err());
+        assert!(parse_window("3000000000000h").is_err());
+        assert!(parse_window("300000000000h").is_err());
+    }
+
+    #[test]
+    fn generates_bash_completions() {
+        let mut out = Vec::new();
+        print_completions(clap_complete::Shell::Bash, &mut out);
+
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.contains("_pji()"));
+        assert!(script.contains("worktree"));
+    }
 }
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Git hosting service, which decides how web URLs for a repository are built.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Provider {
+    #[serde(rename = "github")]
+    GitHub,
+    #[serde(rename = "gitlab")]
+    GitLab,
+    /// Bitbucket Cloud (bitbucket.org)
+    Bitbucket,
+    /// Self-hosted Bitbucket Server / Data Center, where the owner is a project key
+    BitbucketServer,
+    Gitea,
+}
+
+impl Provider {
+    /// Returns the provider of a well-known public host.
+    pub fn from_hostname(hostname: &str) -> Option<Self> {
+        match hostname {
+            "github.com" => Some(Self::GitHub),
+            "gitlab.com" => Some(Self::GitLab),
+            "bitbucket.org" => Some(Self::Bitbucket),
+            _ => None,
+        }
+    }
+}
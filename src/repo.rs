@@ -1,14 +1,46 @@
-use crate::{error::PjiError, util::parse_git_url};
+use crate::{
+    api::Layout,
+    error::PjiError,
+    provider::Provider,
+    util::{parse_git_url, redact_url_credentials},
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
+/// Stored as `"SSH"`, `"HTTP"` or `"GIT"`; older metadata used the variant names.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
 pub(crate) enum GitProtocol {
-    #[serde(rename = "SSH", alias = "Ssh")]
     Ssh,
-    #[serde(rename = "HTTP", alias = "Https")]
     Https,
+    /// The unauthenticated `git://` protocol some mirrors still serve.
+    Git,
+    /// A protocol written by a newer pji. Keeps old versions able to load
+    /// its metadata, and keeps the value so saving doesn't lose it.
+    Unknown(String),
+}
+
+impl From<String> for GitProtocol {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "SSH" | "Ssh" => Self::Ssh,
+            "HTTP" | "Https" => Self::Https,
+            "GIT" | "Git" => Self::Git,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<GitProtocol> for String {
+    fn from(protocol: GitProtocol) -> Self {
+        match protocol {
+            GitProtocol::Ssh => "SSH".to_string(),
+            GitProtocol::Https => "HTTP".to_string(),
+            GitProtocol::Git => "GIT".to_string(),
+            GitProtocol::Unknown(value) => value,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -20,6 +52,15 @@ pub(crate) struct GitURI {
     pub(crate) uri: String,
 }
 
+impl GitURI {
+    /// Whether both URLs point at the same repository, regardless of protocol.
+    pub(crate) fn same_repository(&self, other: &GitURI) -> bool {
+        self.hostname.eq_ignore_ascii_case(&other.hostname)
+            && self.user.eq_ignore_ascii_case(&other.user)
+            && self.repo.eq_ignore_ascii_case(&other.repo)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct PjiRepo {
     pub(crate) git_uri: GitURI,
@@ -27,26 +68,127 @@ pub(crate) struct PjiRepo {
     pub(crate) root: PathBuf,
     pub(crate) create_time: DateTime<Utc>,
     pub(crate) last_open_time: DateTime<Utc>,
+    #[serde(default)]
+    pub(crate) open_count: u32,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    /// Provider set with `pji repo provider`, e.g. for GitHub Enterprise on a
+    /// custom domain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) provider: Option<Provider>,
+    /// The branch `origin/HEAD` points at, recorded by `add` and `scan` or the
+    /// first time a web URL needs it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) default_branch: Option<String>,
 }
 
 impl PjiRepo {
-    pub(crate) fn try_new(repo_uri: &str, root: &Path) -> Result<Self, PjiError> {
-        let git_uri =
-            parse_git_url(repo_uri).ok_or_else(|| PjiError::InvalidGitUrl(repo_uri.to_string()))?;
-        let repo_dir = root
-            .join(&git_uri.hostname)
-            .join(&git_uri.user)
-            .join(&git_uri.repo);
+    pub(crate) fn try_new(repo_uri: &str, root: &Path, layout: Layout) -> Result<Self, PjiError> {
+        let git_uri = parse_git_url(repo_uri)
+            .ok_or_else(|| PjiError::InvalidGitUrl(redact_url_credentials(repo_uri)))?;
+        let repo_dir =
+            root.join(layout.relative_dir(&git_uri.hostname, &git_uri.user, &git_uri.repo));
         Ok(Self {
             git_uri,
             dir: repo_dir,
             root: root.to_path_buf(),
             create_time: Utc::now(),
             last_open_time: Utc::now(),
+            open_count: 0,
+            tags: Vec::new(),
+            provider: None,
+            default_branch: None,
         })
     }
 
     pub(crate) fn update_open_time(&mut self) {
         self.last_open_time = Utc::now();
+        self.open_count = self.open_count.saturating_add(1);
+    }
+
+    /// Adds each tag the repository doesn't have yet; blank tags are ignored.
+    /// Returns the tags that were added.
+    pub(crate) fn add_tags<'a>(&mut self, tags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut added = Vec::new();
+        for tag in tags.into_iter().map(str::trim) {
+            if !tag.is_empty() && !self.has_tag(tag) {
+                self.tags.push(tag.to_string());
+                added.push(tag.to_string());
+            }
+        }
+        added
+    }
+
+    /// Removes the given tags. Returns the tags that were removed.
+    pub(crate) fn remove_tags<'a>(
+        &mut self,
+        tags: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<String> {
+        let mut removed = Vec::new();
+        for tag in tags.into_iter().map(str::trim) {
+            let count = self.tags.len();
+            self.tags.retain(|existing| existing != tag);
+            if self.tags.len() != count {
+                removed.push(tag.to_string());
+            }
+        }
+        removed
+    }
+
+    pub(crate) fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|existing| existing == tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo() -> PjiRepo {
+        PjiRepo::try_new(
+            "git@github.com:zhanba/pji.git",
+            Path::new("/tmp/pji"),
+            Layout::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn add_tags_skips_duplicates_and_blanks() {
+        let mut repo = repo();
+
+        assert_eq!(
+            repo.add_tags(["work", " oss ", "work", ""]),
+            ["work", "oss"]
+        );
+        assert_eq!(repo.add_tags(["oss"]), Vec::<String>::new());
+        assert_eq!(repo.tags, ["work", "oss"]);
+        assert!(repo.has_tag("oss"));
+    }
+
+    #[test]
+    fn builds_the_directory_of_each_layout() {
+        let dir = |layout| {
+            PjiRepo::try_new("git@github.com:zhanba/pji.git", Path::new("/pji"), layout)
+                .unwrap()
+                .dir
+        };
+
+        assert_eq!(
+            dir(Layout::HostUserRepo),
+            Path::new("/pji/github.com/zhanba/pji")
+        );
+        assert_eq!(dir(Layout::UserRepo), Path::new("/pji/zhanba/pji"));
+        assert_eq!(dir(Layout::Flat), Path::new("/pji/pji"));
+    }
+
+    #[test]
+    fn remove_tags_reports_only_tags_that_were_present() {
+        let mut repo = repo();
+        repo.add_tags(["work", "oss"]);
+
+        assert_eq!(repo.remove_tags(["oss", "archived"]), ["oss"]);
+        assert_eq!(repo.tags, ["work"]);
+        assert!(!repo.has_tag("oss"));
     }
 }
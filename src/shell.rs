@@ -0,0 +1,64 @@
+use clap::ValueEnum;
+
+/// Environment variable naming the file the shell function reads the target
+/// directory from. When it is set, pji writes the directory there instead of
+/// opening a new shell.
+pub const CD_FILE_ENV: &str = "PJI_CD_FILE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const POSIX_INIT: &str = r#"pji() {
+    local pji_cd_file pji_status pji_dir
+    pji_cd_file="$(mktemp -t pji-cd.XXXXXX)" || return
+    PJI_CD_FILE="$pji_cd_file" command pji "$@"
+    pji_status=$?
+    pji_dir="$(cat -- "$pji_cd_file")"
+    rm -f -- "$pji_cd_file"
+    if [ -n "$pji_dir" ] && [ -d "$pji_dir" ]; then
+        cd -- "$pji_dir" || return
+    fi
+    return "$pji_status"
+}
+"#;
+
+const FISH_INIT: &str = r#"function pji
+    set -l pji_cd_file (mktemp -t pji-cd.XXXXXX); or return
+    PJI_CD_FILE=$pji_cd_file command pji $argv
+    set -l pji_status $status
+    set -l pji_dir (cat -- $pji_cd_file)
+    rm -f -- $pji_cd_file
+    if test -n "$pji_dir"; and test -d "$pji_dir"
+        cd -- $pji_dir
+    end
+    return $pji_status
+end
+"#;
+
+/// Returns a `pji` shell function that changes the current shell's directory
+/// to the repository or worktree selected by `pji find`, `pji wt`, and friends.
+pub fn init_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash | Shell::Zsh => POSIX_INIT,
+        Shell::Fish => FISH_INIT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_scripts_pass_the_cd_file_to_pji() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = init_script(shell);
+            assert!(script.contains(&format!("{CD_FILE_ENV}=")));
+            assert!(script.contains("command pji"));
+        }
+        assert!(init_script(Shell::Fish).starts_with("function pji"));
+    }
+}
@@ -1,5 +1,8 @@
-use crate::error::PjiError;
-use std::path::{Path, PathBuf};
+use crate::{error::PjiError, git::GitRunner};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 
 /// Represents a single git worktree
@@ -15,6 +18,8 @@ pub struct GitWorktree {
     pub is_main: bool,
     /// Whether the worktree is locked
     pub locked: bool,
+    /// Reason given when the worktree was locked, if any
+    pub lock_reason: Option<String>,
     /// Whether the worktree can be pruned
     pub prunable: bool,
 }
@@ -80,6 +85,7 @@ fn parse_worktree_porcelain(output: &str) -> Vec<GitWorktree> {
     let mut current_branch: Option<String> = None;
     let mut is_bare = false;
     let mut is_locked = false;
+    let mut lock_reason: Option<String> = None;
     let mut is_prunable = false;
 
     for line in output.lines() {
@@ -93,6 +99,7 @@ fn parse_worktree_porcelain(output: &str) -> Vec<GitWorktree> {
                         commit,
                         is_main: worktrees.is_empty(),
                         locked: is_locked,
+                        lock_reason: lock_reason.take(),
                         prunable: is_prunable,
                     });
                 }
@@ -102,6 +109,7 @@ fn parse_worktree_porcelain(output: &str) -> Vec<GitWorktree> {
             current_branch = None;
             is_bare = false;
             is_locked = false;
+            lock_reason = None;
             is_prunable = false;
         } else if let Some(commit) = line.strip_prefix("HEAD ") {
             current_commit = Some(commit.to_string());
@@ -114,8 +122,11 @@ fn parse_worktree_porcelain(output: &str) -> Vec<GitWorktree> {
             }
         } else if line == "bare" {
             is_bare = true;
-        } else if line == "locked" || line.starts_with("locked ") {
+        } else if line == "locked" {
             is_locked = true;
+        } else if let Some(reason) = line.strip_prefix("locked ") {
+            is_locked = true;
+            lock_reason = Some(reason.to_string());
         } else if line == "prunable" || line.starts_with("prunable ") {
             is_prunable = true;
         } else if line == "detached" {
@@ -132,6 +143,7 @@ fn parse_worktree_porcelain(output: &str) -> Vec<GitWorktree> {
                 commit,
                 is_main: worktrees.is_empty(),
                 locked: is_locked,
+                lock_reason,
                 prunable: is_prunable,
             });
         }
@@ -149,15 +161,19 @@ fn parse_worktree_porcelain(output: &str) -> Vec<GitWorktree> {
 /// * `Ok(Some(WorktreeList))` if worktrees are found
 /// * `Ok(None)` if the command succeeds but no worktrees are found
 /// * `Err(PjiError)` if git cannot be executed or returns an error
-pub(crate) fn list_worktrees(repo_dir: &PathBuf) -> Result<Option<WorktreeList>, PjiError> {
+pub(crate) fn list_worktrees(
+    git: &GitRunner,
+    repo_dir: &PathBuf,
+) -> Result<Option<WorktreeList>, PjiError> {
     let command = format!("git -C {} worktree list --porcelain", repo_dir.display());
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(repo_dir)
-        .arg("worktree")
-        .arg("list")
-        .arg("--porcelain")
-        .output()?;
+    let output = git.output(
+        Command::new("git")
+            .arg("-C")
+            .arg(repo_dir)
+            .arg("worktree")
+            .arg("list")
+            .arg("--porcelain"),
+    )?;
 
     if !output.status.success() {
         return Err(PjiError::GitCommand {
@@ -207,25 +223,56 @@ pub(crate) fn get_main_repo_from_worktree(worktree_dir: &Path) -> Option<PathBuf
     if git_path.is_file() {
         // This is a linked worktree, read the .git file
         let content = std::fs::read_to_string(&git_path).ok()?;
-        // Format: "gitdir: /path/to/main/.git/worktrees/name"
-        let gitdir = content.trim().strip_prefix("gitdir: ")?;
-        let gitdir_path = PathBuf::from(gitdir);
+        // Format: "gitdir: /path/to/main/.git/worktrees/name", or relative to
+        // the worktree with `worktree.useRelativePaths`
+        let gitdir = Path::new(content.trim().strip_prefix("gitdir: ")?);
+        let gitdir_path = if gitdir.is_absolute() {
+            gitdir.to_path_buf()
+        } else {
+            worktree_dir.join(gitdir).canonicalize().ok()?
+        };
 
-        // Navigate up from .git/worktrees/name to the main repo
-        // .git/worktrees/name -> .git/worktrees -> .git -> repo
-        let main_git_dir = gitdir_path.parent()?.parent()?.parent()?;
-        return Some(main_git_dir.to_path_buf());
+        let common_dir = common_git_dir(&gitdir_path)?;
+        // A regular repository keeps its git dir in `repo/.git`, while a bare
+        // repository such as `repo.git` is the git dir itself.
+        return if common_dir.file_name()? == ".git" {
+            common_dir.parent().map(Path::to_path_buf)
+        } else {
+            Some(common_dir)
+        };
     }
 
     None
 }
 
+/// Resolves the git dir shared by all worktrees from the private git dir of
+/// a linked worktree, e.g. `repo/.git/worktrees/name` or
+/// `repo.git/worktrees/name`.
+fn common_git_dir(gitdir: &Path) -> Option<PathBuf> {
+    let Ok(commondir) = fs::read_to_string(gitdir.join("commondir")) else {
+        // Older git doesn't write `commondir`; the worktree's git dir then
+        // always sits in `<common dir>/worktrees/<name>`.
+        return gitdir.parent()?.parent().map(Path::to_path_buf);
+    };
+    let mut common_dir = gitdir.to_path_buf();
+    for component in Path::new(commondir.trim()).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                common_dir.pop();
+            }
+            component => common_dir.push(component),
+        }
+    }
+    Some(common_dir)
+}
+
 /// Add a new worktree
 ///
 /// # Arguments
 /// * `repo_dir` - Path to the repository
 /// * `branch` - Branch name to checkout (or create with -b)
-/// * `path` - Optional path for the worktree (defaults to {repo}.worktrees/{branch})
+/// * `worktree_path` - Path for the worktree, see [`get_default_worktree_path`]
 /// * `create_branch` - If true, create a new branch
 /// * `base_branch` - Optional base branch for new branches (only used when create_branch is true)
 ///
@@ -233,32 +280,13 @@ pub(crate) fn get_main_repo_from_worktree(worktree_dir: &Path) -> Option<PathBuf
 /// * `Ok(PathBuf)` - Path to the created worktree
 /// * `Err(String)` - Error message
 pub(crate) fn add_worktree(
+    git: &GitRunner,
     repo_dir: &PathBuf,
     branch: &str,
-    path: Option<PathBuf>,
+    worktree_path: PathBuf,
     create_branch: bool,
     base_branch: Option<&str>,
 ) -> Result<PathBuf, String> {
-    // Determine worktree path
-    let worktree_path = match path {
-        Some(p) => p,
-        None => {
-            // Default: {repo}.worktrees/{branch}
-            let repo_name = repo_dir
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("repo");
-            let worktrees_dir = repo_dir
-                .parent()
-                .ok_or("Cannot determine parent directory")?
-                .join(format!("{}.worktrees", repo_name));
-
-            // Sanitize branch name for filesystem
-            let safe_branch = branch.replace('/', "-");
-            worktrees_dir.join(&safe_branch)
-        }
-    };
-
     // Build the git worktree add command
     let mut cmd = Command::new("git");
     cmd.arg("-C").arg(repo_dir).arg("worktree").arg("add");
@@ -278,7 +306,7 @@ pub(crate) fn add_worktree(
         cmd.arg(branch);
     }
 
-    let output = cmd.output().map_err(|e| e.to_string())?;
+    let output = git.output(&mut cmd).map_err(|e| e.to_string())?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -293,22 +321,59 @@ pub(crate) fn add_worktree(
 /// # Arguments
 /// * `repo_dir` - Path to the repository
 /// * `branch` - Branch name
+/// * `worktrees_dir` - The repository's directory under the `worktree_base`
+///   config, if set
 ///
 /// # Returns
-/// * `PathBuf` - Default worktree path
-pub(crate) fn get_default_worktree_path(repo_dir: &Path, branch: &str) -> PathBuf {
+/// * `PathBuf` - `{worktrees_dir}/{branch}`, or `{repo}.worktrees/{branch}`
+///   beside the repository without a base
+pub(crate) fn get_default_worktree_path(
+    repo_dir: &Path,
+    branch: &str,
+    worktrees_dir: Option<&Path>,
+) -> PathBuf {
+    // Sanitize branch name for filesystem (replace / with -)
+    let safe_branch = branch.replace('/', "-");
+    get_worktrees_dir(repo_dir, worktrees_dir).join(&safe_branch)
+}
+
+/// Get the directory that holds the default worktrees of a repository
+fn get_worktrees_dir(repo_dir: &Path, worktrees_dir: Option<&Path>) -> PathBuf {
+    if let Some(dir) = worktrees_dir {
+        return dir.to_path_buf();
+    }
     let repo_name = repo_dir
         .file_name()
         .and_then(|n| n.to_str())
         .unwrap_or("repo");
-    let worktrees_dir = repo_dir
+    repo_dir
         .parent()
         .map(|p| p.join(format!("{}.worktrees", repo_name)))
-        .unwrap_or_else(|| PathBuf::from(format!("{}.worktrees", repo_name)));
+        .unwrap_or_else(|| PathBuf::from(format!("{}.worktrees", repo_name)))
+}
 
-    // Sanitize branch name for filesystem (replace / with -)
-    let safe_branch = branch.replace('/', "-");
-    worktrees_dir.join(&safe_branch)
+/// Remove the directory of default worktrees left behind once all of its
+/// worktrees are gone
+///
+/// # Returns
+/// * `Ok(true)` - The directory existed without subdirectories and was removed
+/// * `Ok(false)` - The directory is missing or still contains directories
+pub(crate) fn remove_empty_worktrees_dir(
+    repo_dir: &Path,
+    worktrees_dir: Option<&Path>,
+) -> io::Result<bool> {
+    let worktrees_dir = get_worktrees_dir(repo_dir, worktrees_dir);
+    if !worktrees_dir.is_dir() {
+        return Ok(false);
+    }
+
+    for entry in fs::read_dir(&worktrees_dir)? {
+        if entry?.file_type()?.is_dir() {
+            return Ok(false);
+        }
+    }
+    fs::remove_dir_all(&worktrees_dir)?;
+    Ok(true)
 }
 
 /// Remove a worktree
@@ -318,6 +383,7 @@ pub(crate) fn get_default_worktree_path(repo_dir: &Path, branch: &str) -> PathBu
 /// * `worktree_path` - Path to the worktree to remove
 /// * `force` - If true, force removal even if the worktree is dirty
 pub(crate) fn remove_worktree(
+    git: &GitRunner,
     repo_dir: &PathBuf,
     worktree_path: &PathBuf,
     force: bool,
@@ -331,7 +397,115 @@ pub(crate) fn remove_worktree(
 
     cmd.arg(worktree_path);
 
-    let output = cmd.output().map_err(|e| e.to_string())?;
+    let output = git.output(&mut cmd).map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.to_string());
+    }
+
+    Ok(())
+}
+
+/// Move a linked worktree to a new path
+///
+/// # Arguments
+/// * `repo_dir` - Path to the repository
+/// * `from` - Current path of the worktree
+/// * `to` - New path of the worktree
+///
+/// # Returns
+/// * `Ok(())` - Worktree moved successfully
+/// * `Err(String)` - Error message
+pub(crate) fn move_worktree(
+    git: &GitRunner,
+    repo_dir: &Path,
+    from: &Path,
+    to: &Path,
+) -> Result<(), String> {
+    let output = git
+        .output(Command::new("git").args(move_worktree_args(repo_dir, from, to)))
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.to_string());
+    }
+
+    Ok(())
+}
+
+/// Arguments of `git worktree move` for [`move_worktree`]
+fn move_worktree_args(repo_dir: &Path, from: &Path, to: &Path) -> Vec<OsString> {
+    vec![
+        OsString::from("-C"),
+        repo_dir.into(),
+        "worktree".into(),
+        "move".into(),
+        from.into(),
+        to.into(),
+    ]
+}
+
+/// Lock a worktree so `git worktree prune` and `remove` leave it alone
+///
+/// # Arguments
+/// * `repo_dir` - Path to the repository
+/// * `worktree_path` - Path to the worktree to lock
+/// * `reason` - Optional reason shown by `git worktree list`
+///
+/// # Returns
+/// * `Ok(())` - Worktree locked successfully
+/// * `Err(String)` - Error message
+pub(crate) fn lock_worktree(
+    git: &GitRunner,
+    repo_dir: &PathBuf,
+    worktree_path: &PathBuf,
+    reason: Option<&str>,
+) -> Result<(), String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(repo_dir).arg("worktree").arg("lock");
+
+    if let Some(reason) = reason {
+        cmd.arg("--reason").arg(reason);
+    }
+
+    cmd.arg(worktree_path);
+
+    let output = git.output(&mut cmd).map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.to_string());
+    }
+
+    Ok(())
+}
+
+/// Unlock a worktree locked with [`lock_worktree`]
+///
+/// # Arguments
+/// * `repo_dir` - Path to the repository
+/// * `worktree_path` - Path to the worktree to unlock
+///
+/// # Returns
+/// * `Ok(())` - Worktree unlocked successfully
+/// * `Err(String)` - Error message
+pub(crate) fn unlock_worktree(
+    git: &GitRunner,
+    repo_dir: &PathBuf,
+    worktree_path: &PathBuf,
+) -> Result<(), String> {
+    let output = git
+        .output(
+            Command::new("git")
+                .arg("-C")
+                .arg(repo_dir)
+                .arg("worktree")
+                .arg("unlock")
+                .arg(worktree_path),
+        )
+        .map_err(|e| e.to_string())?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -348,14 +522,15 @@ pub(crate) fn remove_worktree(
 ///
 /// # Returns
 /// * `Vec<String>` - List of local branch names
-pub(crate) fn list_local_branches(repo_dir: &PathBuf) -> Vec<String> {
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(repo_dir)
-        .arg("branch")
-        .arg("--list")
-        .arg("--format=%(refname:short)")
-        .output();
+pub(crate) fn list_local_branches(git: &GitRunner, repo_dir: &PathBuf) -> Vec<String> {
+    let output = git.output(
+        Command::new("git")
+            .arg("-C")
+            .arg(repo_dir)
+            .arg("branch")
+            .arg("--list")
+            .arg("--format=%(refname:short)"),
+    );
 
     match output {
         Ok(output) if output.status.success() => {
@@ -377,14 +552,15 @@ pub(crate) fn list_local_branches(repo_dir: &PathBuf) -> Vec<String> {
 ///
 /// # Returns
 /// * `Vec<String>` - List of remote branch names (e.g., "origin/main")
-pub(crate) fn list_remote_branches(repo_dir: &PathBuf) -> Vec<String> {
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(repo_dir)
-        .arg("branch")
-        .arg("-r")
-        .arg("--format=%(refname:short)")
-        .output();
+pub(crate) fn list_remote_branches(git: &GitRunner, repo_dir: &PathBuf) -> Vec<String> {
+    let output = git.output(
+        Command::new("git")
+            .arg("-C")
+            .arg(repo_dir)
+            .arg("branch")
+            .arg("-r")
+            .arg("--format=%(refname:short)"),
+    );
 
     match output {
         Ok(output) if output.status.success() => {
@@ -400,14 +576,16 @@ pub(crate) fn list_remote_branches(repo_dir: &PathBuf) -> Vec<String> {
 }
 
 /// Prune stale worktree information
-pub(crate) fn prune_worktrees(repo_dir: &PathBuf) -> Result<String, String> {
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(repo_dir)
-        .arg("worktree")
-        .arg("prune")
-        .arg("-v")
-        .output()
+pub(crate) fn prune_worktrees(git: &GitRunner, repo_dir: &PathBuf) -> Result<String, String> {
+    let output = git
+        .output(
+            Command::new("git")
+                .arg("-C")
+                .arg(repo_dir)
+                .arg("worktree")
+                .arg("prune")
+                .arg("-v"),
+        )
         .map_err(|e| e.to_string())?;
 
     if !output.status.success() {
@@ -494,6 +672,130 @@ locked
         assert!(worktrees[1].locked);
     }
 
+    #[test]
+    fn test_parse_worktree_porcelain_locked_with_reason() {
+        let output = r#"worktree /home/user/repo
+HEAD abc123
+branch refs/heads/main
+
+worktree /home/user/repo.worktrees/usb
+HEAD def456
+branch refs/heads/usb
+locked on a removable drive
+
+worktree /home/user/repo.worktrees/feature
+HEAD 789abc
+branch refs/heads/feature
+"#;
+        let worktrees = parse_worktree_porcelain(output);
+        assert_eq!(worktrees.len(), 3);
+        assert!(worktrees[1].locked);
+        assert_eq!(
+            worktrees[1].lock_reason.as_deref(),
+            Some("on a removable drive")
+        );
+        assert!(!worktrees[2].locked);
+        assert_eq!(worktrees[2].lock_reason, None);
+    }
+
+    #[test]
+    fn removes_only_empty_worktrees_dir() {
+        let base = std::env::temp_dir().join(format!("pji-worktrees-dir-{}", std::process::id()));
+        let empty_repo = base.join("empty");
+        let busy_repo = base.join("busy");
+        fs::create_dir_all(base.join("empty.worktrees")).unwrap();
+        fs::create_dir_all(base.join("busy.worktrees/feature")).unwrap();
+
+        assert!(remove_empty_worktrees_dir(&empty_repo, None).unwrap());
+        assert!(!base.join("empty.worktrees").exists());
+
+        assert!(!remove_empty_worktrees_dir(&busy_repo, None).unwrap());
+        assert!(base.join("busy.worktrees/feature").is_dir());
+
+        assert!(!remove_empty_worktrees_dir(&base.join("missing"), None).unwrap());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn builds_worktree_move_args() {
+        let args = move_worktree_args(
+            Path::new("/work/pji"),
+            Path::new("/work/pji.worktrees/feature"),
+            Path::new("/home/me/worktrees/pji/feature"),
+        );
+
+        assert_eq!(
+            args,
+            [
+                "-C",
+                "/work/pji",
+                "worktree",
+                "move",
+                "/work/pji.worktrees/feature",
+                "/home/me/worktrees/pji/feature",
+            ]
+        );
+    }
+
+    #[test]
+    fn builds_default_worktree_paths() {
+        let repo = Path::new("/work/github.com/zhanba/pji");
+
+        assert_eq!(
+            get_default_worktree_path(repo, "feature/login", None),
+            Path::new("/work/github.com/zhanba/pji.worktrees/feature-login")
+        );
+        assert_eq!(
+            get_default_worktree_path(
+                repo,
+                "feature/login",
+                Some(Path::new("/home/me/worktrees/github.com/zhanba/pji"))
+            ),
+            Path::new("/home/me/worktrees/github.com/zhanba/pji/feature-login")
+        );
+    }
+
+    #[test]
+    fn finds_main_repo_from_relative_gitdir() {
+        let base = std::env::temp_dir().join(format!("pji-relative-gitdir-{}", std::process::id()));
+        let repo = base.join("pji");
+        let worktree = base.join("pji.worktrees/feature");
+        fs::create_dir_all(repo.join(".git/worktrees/feature")).unwrap();
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(
+            worktree.join(".git"),
+            "gitdir: ../../pji/.git/worktrees/feature\n",
+        )
+        .unwrap();
+
+        let main = get_main_repo_from_worktree(&worktree);
+        let expected = repo.canonicalize().unwrap();
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(main, Some(expected));
+    }
+
+    #[test]
+    fn finds_bare_main_repo_from_gitdir() {
+        let base = std::env::temp_dir().join(format!("pji-bare-gitdir-{}", std::process::id()));
+        let repo = base.join("pji.git");
+        let worktree = base.join("feature");
+        let gitdir = repo.join("worktrees/feature");
+        fs::create_dir_all(&gitdir).unwrap();
+        fs::write(gitdir.join("commondir"), "../..\n").unwrap();
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", gitdir.display()),
+        )
+        .unwrap();
+
+        let main = get_main_repo_from_worktree(&worktree);
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(main, Some(repo));
+    }
+
     #[test]
     fn test_worktree_display_name() {
         let main_wt = GitWorktree {
@@ -502,6 +804,7 @@ locked
             commit: "abc123".to_string(),
             is_main: true,
             locked: false,
+            lock_reason: None,
             prunable: false,
         };
         assert_eq!(main_wt.display_name(), "main (main)");
@@ -512,6 +815,7 @@ locked
             commit: "def456".to_string(),
             is_main: false,
             locked: false,
+            lock_reason: None,
             prunable: false,
         };
         assert_eq!(linked_wt.display_name(), "feature");
@@ -522,6 +826,7 @@ locked
             commit: "abc123def456".to_string(),
             is_main: false,
             locked: false,
+            lock_reason: None,
             prunable: false,
         };
         assert_eq!(detached_wt.display_name(), "abc123de");